@@ -0,0 +1,287 @@
+use crate::math::{exp, ln};
+
+/// An accumulator which holds the state of the 1-pass (online) algorithm used by
+/// [`LogSumExp`](crate::LogSumExp), so that values may be supplied incrementally
+/// and partial results from disjoint subsequences may be combined.
+///
+/// The state consists of the running maximum and the sum of exponentials scaled by
+/// the running maximum. Special values are handled identically to `LogSumExp`:
+/// `-inf` contributes nothing, `+inf` saturates the result and `nan` is absorbing.
+/// If more than one `nan` is encountered, the first is retained.
+///
+/// # Examples
+/// ```
+/// use logsumexp::{LogSumExp, OnlineLogSumExp};
+///
+/// let v: Vec<f64> = vec![0.5, 1.0, 1.5, 2.0, 2.5];
+///
+/// let mut acc = OnlineLogSumExp::<f64>::new();
+/// for x in v.iter() {
+///     acc.push(*x);
+/// }
+/// assert_eq!(acc.finish(), v.iter().ln_sum_exp());
+///
+/// // combine partial results
+/// let mut lhs: OnlineLogSumExp<f64> = v[..2].iter().collect();
+/// let rhs: OnlineLogSumExp<f64> = v[2..].iter().collect();
+/// lhs.merge(&rhs);
+/// assert!((lhs.finish() - v.iter().ln_sum_exp()).abs() < f64::EPSILON);
+///
+/// // special values
+/// let mut acc = OnlineLogSumExp::<f64>::new();
+/// assert_eq!(acc.finish(), f64::NEG_INFINITY);
+/// acc.extend([0.5, f64::INFINITY]);
+/// assert_eq!(acc.finish(), f64::INFINITY);
+/// acc.push(f64::NAN);
+/// assert!(acc.finish().is_nan());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OnlineLogSumExp<T> {
+    max: T,
+    sum: T,
+}
+
+macro_rules! impl_online_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl OnlineLogSumExp<$f> {
+                /// Create an empty accumulator, i.e. the state for an empty sequence.
+                pub fn new() -> Self {
+                    Self {
+                        max: $f::NEG_INFINITY,
+                        sum: 0.0,
+                    }
+                }
+
+                /// Update the state with a single value.
+                #[inline]
+                pub fn push(&mut self, x: $f) {
+                    self.push_scaled(x, 1.0);
+                }
+
+                // Update the state with a single value, `x`, which enters the sum as
+                // `exp(x / tau)`, for `tau > 0`. The running maximum is kept on the
+                // unscaled values, so that `x / tau` is never formed; the state must be
+                // finished by `finish_scaled` with the same `tau`.
+                #[inline]
+                pub(crate) fn push_scaled(&mut self, x: $f, tau: $f) {
+                    if self.max.is_nan() || x == $f::NEG_INFINITY {
+                        // nan is absorbing; -inf contributes nothing.
+                    } else if x == $f::INFINITY || x.is_nan() {
+                        self.max = x;
+                    } else if self.max != $f::INFINITY {
+                        // finite and not nan
+                        let m_new = self.max.max(x);
+                        self.sum = self.sum * exp((self.max - m_new) / tau) + exp((x - m_new) / tau);
+                        self.max = m_new;
+                    }
+                }
+
+                /// Update the state with a single value, `x`, which enters the sum
+                /// as `w * exp(x)`. The weight is on the linear scale, hence, must be
+                /// non-negative; a negative or `nan` weight results in `nan`.
+                /// A term with zero weight is excluded, irrespective of its value.
+                pub fn push_weighted(&mut self, x: $f, w: $f) {
+                    if w == 0.0 {
+                        // Zero weight: the term does not participate.
+                    } else if w.is_nan() {
+                        self.push(w);
+                    } else if w < 0.0 {
+                        self.push($f::NAN);
+                    } else if w == $f::INFINITY {
+                        // -inf + inf is nan, as is 0 * inf on the linear scale.
+                        self.push(x + w);
+                    } else {
+                        // On the log scale, so that a large weight cannot overflow the sum.
+                        self.push_ln_weighted(x, ln(w));
+                    }
+                }
+
+                /// Update the state with a single value, `x`, which enters the sum
+                /// as `exp(x + ln_w)`, i.e. the weight is on the log scale.
+                /// A term with a log-weight of `-inf` (zero weight) is excluded,
+                /// irrespective of its value.
+                pub fn push_ln_weighted(&mut self, x: $f, ln_w: $f) {
+                    if ln_w != $f::NEG_INFINITY {
+                        self.push(x + ln_w);
+                    }
+                }
+
+                // The state with running maximum `max` and sum of exponentials scaled by
+                // `max`, which is ignored unless `max` is finite.
+                pub(crate) fn from_parts(max: $f, sum: $f) -> Self {
+                    Self { max, sum }
+                }
+
+                /// Combine the state of `other` into `self`, such that the result is
+                /// the state which would have been obtained by pushing the values of
+                /// `other` after those of `self`.
+                pub fn merge(&mut self, other: &Self) {
+                    if self.max.is_nan() || other.max == $f::NEG_INFINITY {
+                        // Nothing to do.
+                    } else if other.max.is_nan() || self.max == $f::NEG_INFINITY {
+                        *self = *other;
+                    } else if self.max == $f::INFINITY || other.max == $f::INFINITY {
+                        self.max = $f::INFINITY;
+                    } else {
+                        let m_new = self.max.max(other.max);
+                        self.sum = self.sum * exp(self.max - m_new)
+                            + other.sum * exp(other.max - m_new);
+                        self.max = m_new;
+                    }
+                }
+
+                /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of
+                /// the values accumulated thus far.
+                #[inline]
+                pub fn finish(&self) -> $f {
+                    self.finish_scaled(1.0)
+                }
+
+                // Return `tau` times the LogSumExp of the values scaled by `1 / tau`, i.e.
+                // the counterpart of `finish` for `push_scaled`.
+                #[inline]
+                pub(crate) fn finish_scaled(&self, tau: $f) -> $f {
+                    if self.max.is_finite() {
+                        self.max + tau * ln(self.sum)
+                    } else {
+                        self.max
+                    }
+                }
+            }
+
+            impl Default for OnlineLogSumExp<$f> {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            impl Extend<$f> for OnlineLogSumExp<$f> {
+                fn extend<I: IntoIterator<Item = $f>>(&mut self, iter: I) {
+                    iter.into_iter().for_each(|x| self.push(x));
+                }
+            }
+
+            impl<'a> Extend<&'a $f> for OnlineLogSumExp<$f> {
+                fn extend<I: IntoIterator<Item = &'a $f>>(&mut self, iter: I) {
+                    iter.into_iter().for_each(|x| self.push(*x));
+                }
+            }
+
+            impl FromIterator<$f> for OnlineLogSumExp<$f> {
+                fn from_iter<I: IntoIterator<Item = $f>>(iter: I) -> Self {
+                    let mut acc = Self::new();
+                    acc.extend(iter);
+                    acc
+                }
+            }
+
+            impl<'a> FromIterator<&'a $f> for OnlineLogSumExp<$f> {
+                fn from_iter<I: IntoIterator<Item = &'a $f>>(iter: I) -> Self {
+                    let mut acc = Self::new();
+                    acc.extend(iter);
+                    acc
+                }
+            }
+        )+
+    }
+}
+impl_online_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! online_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn cases() -> Vec<Vec<$f>> {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+                    vec![
+                        vec![],
+                        vec![x],
+                        vec![neg_inf],
+                        vec![inf],
+                        vec![nan],
+                        vec![neg_inf, x, neg_inf, neg_inf],
+                        vec![neg_inf, x, y, neg_inf],
+                        vec![x, inf, y, neg_inf],
+                        vec![neg_inf, neg_inf, neg_inf, inf],
+                        vec![inf, neg_inf, neg_inf, inf],
+                        vec![x, y, x, y, x, y],
+                        vec![x, inf, nan, y],
+                        vec![nan, x, y, inf],
+                        vec![inf, inf, neg_inf, nan],
+                        vec![neg_inf, neg_inf, neg_inf, nan],
+                        vec![x, y, nan],
+                    ]
+                }
+
+                fn same(lhs: $f, rhs: $f) -> bool {
+                    (lhs.is_nan() && rhs.is_nan()) || lhs == rhs
+                }
+
+                #[test]
+                fn push_matches_ln_sum_exp() {
+                    for v in cases() {
+                        let mut acc = OnlineLogSumExp::<$f>::new();
+                        for x in v.iter() {
+                            acc.push(*x);
+                        }
+                        assert!(same(acc.finish(), v.iter().ln_sum_exp()), "{:?}", v);
+
+                        let acc: OnlineLogSumExp<$f> = v.iter().collect();
+                        assert!(same(acc.finish(), v.iter().ln_sum_exp()), "{:?}", v);
+
+                        let acc: OnlineLogSumExp<$f> = v.clone().into_iter().collect();
+                        assert!(same(acc.finish(), v.iter().ln_sum_exp()), "{:?}", v);
+                    }
+                }
+
+                #[test]
+                fn merge_works() {
+                    for v in cases() {
+                        let rhs = v.iter().ln_sum_exp();
+                        for k in 0..=v.len() {
+                            let mut lhs: OnlineLogSumExp<$f> = v[..k].iter().collect();
+                            let other: OnlineLogSumExp<$f> = v[k..].iter().collect();
+                            lhs.merge(&other);
+                            let lhs = lhs.finish();
+                            if rhs.is_finite() {
+                                assert!((lhs - rhs).abs() < 4.0 * $f::EPSILON, "{:?} {}", v, k);
+                            } else {
+                                assert!(same(lhs, rhs), "{:?} {}", v, k);
+                            }
+                        }
+                    }
+                }
+
+                #[test]
+                fn merge_empty_is_identity() {
+                    let v: Vec<$f> = vec![0.5, 1.0, 1.5];
+                    let acc: OnlineLogSumExp<$f> = v.iter().collect();
+
+                    let mut lhs = acc;
+                    lhs.merge(&OnlineLogSumExp::default());
+                    assert_eq!(lhs, acc);
+
+                    let mut lhs = OnlineLogSumExp::<$f>::default();
+                    lhs.merge(&acc);
+                    assert_eq!(lhs, acc);
+                }
+            }
+        }
+    }
+    online_logsumexp_tests! { f64_online_logsumexp_impl f64 }
+    online_logsumexp_tests! { f32_online_logsumexp_impl f32 }
+}
@@ -0,0 +1,267 @@
+use crate::{LogSumExp, OnlineLogSumExp, SliceLogSumExp, Softmax};
+use ndarray::{Array, ArrayBase, Axis, Data, RemoveAxis, Zip};
+
+/// Extension methods for [`ndarray`] arrays, which evaluate
+/// [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) and softmax along an axis,
+/// or over all elements. The handling of +/-infinity and `nan` is identical to that of
+/// [`LogSumExp`] and [`Softmax`], respectively.
+///
+/// Lanes which are contiguous in memory are reduced by the vectorized kernel of
+/// [`SliceLogSumExp`]; otherwise, an [`OnlineLogSumExp`] state is accumulated for each
+/// lane while traversing the array one subview at a time.
+pub trait ArrayLogSumExp<A, D: RemoveAxis> {
+    /// Return the LogSumExp of all elements of the array.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::ArrayLogSumExp;
+    /// use ndarray::array;
+    ///
+    /// let a = array![[0.0_f64, 0.0], [0.0, 0.0]];
+    /// assert!((a.ln_sum_exp_all() - 4.0_f64.ln()).abs() < f64::EPSILON);
+    /// ```
+    fn ln_sum_exp_all(&self) -> A;
+
+    /// Return the LogSumExp of each lane along `axis`, with `axis` removed from the
+    /// shape of the result.
+    ///
+    /// **Panics** if `axis` is out of bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::ArrayLogSumExp;
+    /// use ndarray::{array, Axis};
+    ///
+    /// let a = array![[0.0_f64, f64::NEG_INFINITY], [1.0, f64::NEG_INFINITY]];
+    /// let rhs = 0.0_f64 + 1.0_f64.exp().ln_1p();
+    /// assert_eq!(a.ln_sum_exp_axis(Axis(0)), array![rhs, f64::NEG_INFINITY]);
+    /// assert_eq!(a.ln_sum_exp_axis(Axis(1)), array![0.0, 1.0]);
+    /// ```
+    fn ln_sum_exp_axis(&self, axis: Axis) -> Array<A, D::Smaller>;
+
+    /// Return the LogSumExp of each lane along `axis`, with `axis` retained, with
+    /// length 1, in the shape of the result, so that it may be broadcast against
+    /// the array.
+    ///
+    /// **Panics** if `axis` is out of bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::ArrayLogSumExp;
+    /// use ndarray::{array, Axis};
+    ///
+    /// let a = array![[0.0_f64, 1.0], [2.0, 3.0]];
+    /// let lse = a.ln_sum_exp_axis_keepdims(Axis(1));
+    /// assert_eq!(lse.shape(), &[2, 1]);
+    /// let log_p = &a - &lse;
+    /// assert_eq!(log_p, a.log_softmax_axis(Axis(1)));
+    /// ```
+    fn ln_sum_exp_axis_keepdims(&self, axis: Axis) -> Array<A, D>;
+
+    /// Return the softmax of each lane along `axis`.
+    ///
+    /// **Panics** if `axis` is out of bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::ArrayLogSumExp;
+    /// use ndarray::{array, Axis};
+    ///
+    /// let a = array![[1.0_f64, 1.0], [f64::INFINITY, 1.0]];
+    /// let p = a.softmax_axis(Axis(1));
+    /// assert!(p.row(0).iter().all(|p| (p - 0.5).abs() < f64::EPSILON));
+    /// assert_eq!(p.row(1), array![1.0, 0.0]);
+    /// ```
+    fn softmax_axis(&self, axis: Axis) -> Array<A, D>;
+
+    /// Return the log-softmax of each lane along `axis`.
+    ///
+    /// **Panics** if `axis` is out of bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::ArrayLogSumExp;
+    /// use ndarray::{array, Axis};
+    ///
+    /// let a = array![[1.0_f64, 1.0], [f64::NEG_INFINITY, 1.0]];
+    /// let log_p = a.log_softmax_axis(Axis(0));
+    /// assert_eq!(log_p.column(0), array![0.0, f64::NEG_INFINITY]);
+    /// assert!(log_p.column(1).iter().all(|lp| (lp + 2.0_f64.ln()).abs() < f64::EPSILON));
+    /// ```
+    fn log_softmax_axis(&self, axis: Axis) -> Array<A, D>;
+}
+
+macro_rules! impl_array_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<S, D> ArrayLogSumExp<$f, D> for ArrayBase<S, D>
+            where
+                S: Data<Elem = $f>,
+                D: RemoveAxis,
+            {
+                fn ln_sum_exp_all(&self) -> $f {
+                    match self.as_slice_memory_order() {
+                        Some(slice) => slice.ln_sum_exp_slice(),
+                        None => self.iter().ln_sum_exp(),
+                    }
+                }
+
+                fn ln_sum_exp_axis(&self, axis: Axis) -> Array<$f, D::Smaller> {
+                    if self.len_of(axis) <= 1 || self.stride_of(axis) == 1 {
+                        self.map_axis(axis, |lane| match lane.as_slice() {
+                            Some(slice) => slice.ln_sum_exp_slice(),
+                            None => lane.iter().ln_sum_exp(),
+                        })
+                    } else {
+                        let mut acc = Array::from_elem(
+                            self.raw_dim().remove_axis(axis),
+                            OnlineLogSumExp::<$f>::new(),
+                        );
+                        for subview in self.axis_iter(axis) {
+                            Zip::from(&mut acc).and(&subview).for_each(|acc, x| acc.push(*x));
+                        }
+                        acc.map(|acc| acc.finish())
+                    }
+                }
+
+                fn ln_sum_exp_axis_keepdims(&self, axis: Axis) -> Array<$f, D> {
+                    // Re-inserting the removed axis always restores the dimensionality.
+                    self.ln_sum_exp_axis(axis)
+                        .insert_axis(axis)
+                        .into_dimensionality::<D>()
+                        .unwrap()
+                }
+
+                fn softmax_axis(&self, axis: Axis) -> Array<$f, D> {
+                    let mut out = self.to_owned();
+                    for mut lane in out.lanes_mut(axis) {
+                        match lane.as_slice_mut() {
+                            Some(slice) => slice.softmax_mut(),
+                            None => {
+                                let v = lane.to_vec().softmax();
+                                lane.iter_mut().zip(v).for_each(|(x, p)| *x = p);
+                            }
+                        }
+                    }
+                    out
+                }
+
+                fn log_softmax_axis(&self, axis: Axis) -> Array<$f, D> {
+                    let mut out = self.to_owned();
+                    for mut lane in out.lanes_mut(axis) {
+                        match lane.as_slice_mut() {
+                            Some(slice) => slice.log_softmax_mut(),
+                            None => {
+                                let v = lane.to_vec().log_softmax();
+                                lane.iter_mut().zip(v).for_each(|(x, p)| *x = p);
+                            }
+                        }
+                    }
+                    out
+                }
+            }
+        )+
+    }
+}
+impl_array_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{array, s, Array2, Array3, Ix1};
+
+    macro_rules! array_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn same(lhs: $f, rhs: $f) -> bool {
+                    (lhs.is_nan() && rhs.is_nan())
+                        || lhs == rhs
+                        || (lhs - rhs).abs() < 16.0 * $f::EPSILON * rhs.abs().max(1.0)
+                }
+
+                fn array3() -> Array3<$f> {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let mut a = Array3::from_shape_fn((3, 4, 5), |(i, j, k)| {
+                        ((i * 20 + j * 5 + k) as $f) / 10.0 - 3.0
+                    });
+                    a[[0, 0, 0]] = inf;
+                    a[[1, 1, 1]] = neg_inf;
+                    a[[2, 2, 2]] = nan;
+                    a.slice_mut(s![1, 3, ..]).fill(neg_inf);
+                    a
+                }
+
+                #[test]
+                fn ln_sum_exp_axis_works() {
+                    let a = array3();
+                    for axis in 0..3 {
+                        let axis = Axis(axis);
+                        let lhs = a.ln_sum_exp_axis(axis);
+                        let rhs = a.map_axis(axis, |lane| lane.iter().ln_sum_exp());
+                        assert_eq!(lhs.shape(), rhs.shape());
+                        Zip::from(&lhs).and(&rhs).for_each(|l, r| assert!(same(*l, *r), "{} {}", l, r));
+
+                        // non-standard layout
+                        let b = a.t();
+                        let lhs = b.ln_sum_exp_axis(axis);
+                        let rhs = b.map_axis(axis, |lane| lane.iter().ln_sum_exp());
+                        Zip::from(&lhs).and(&rhs).for_each(|l, r| assert!(same(*l, *r), "{} {}", l, r));
+
+                        let lhs = a.ln_sum_exp_axis_keepdims(axis);
+                        let mut shape = a.shape().to_vec();
+                        shape[axis.index()] = 1;
+                        assert_eq!(lhs.shape(), &shape[..]);
+                        let rhs = a.ln_sum_exp_axis(axis);
+                        Zip::from(&lhs.remove_axis(axis)).and(&rhs).for_each(|l, r| assert!(same(*l, *r)));
+                    }
+
+                    // empty axis
+                    let a = Array2::<$f>::zeros((0, 3));
+                    assert_eq!(a.ln_sum_exp_axis(Axis(0)), ndarray::Array1::from_elem(3, $f::NEG_INFINITY));
+                    assert_eq!(a.ln_sum_exp_axis(Axis(1)).len(), 0);
+                }
+
+                #[test]
+                fn ln_sum_exp_all_works() {
+                    let a: Array2<$f> = array![[0.5, 1.0], [1.5, 2.0]];
+                    let rhs = a.iter().ln_sum_exp();
+                    assert!(same(a.ln_sum_exp_all(), rhs));
+                    assert!(same(a.t().ln_sum_exp_all(), rhs));
+                    assert!(same(a.slice(s![.., 1]).into_dimensionality::<Ix1>().unwrap().ln_sum_exp_all(), [1.0 as $f, 2.0].iter().ln_sum_exp()));
+
+                    let a = array3();
+                    assert!(a.ln_sum_exp_all().is_nan());
+                    let a = a.slice(s![..2, .., ..]).to_owned();
+                    assert_eq!(a.ln_sum_exp_all(), $f::INFINITY);
+                }
+
+                #[test]
+                fn softmax_axis_works() {
+                    let a = array3();
+                    for axis in 0..3 {
+                        let axis = Axis(axis);
+                        for b in [a.view(), a.t()] {
+                            let p = b.softmax_axis(axis);
+                            let lp = b.log_softmax_axis(axis);
+                            assert_eq!(p.shape(), b.shape());
+                            for ((lane, p_lane), lp_lane) in b.lanes(axis).into_iter().zip(p.lanes(axis)).zip(lp.lanes(axis)) {
+                                let v = lane.to_vec();
+                                let rhs = v.softmax();
+                                p_lane.iter().zip(rhs.iter()).for_each(|(l, r)| assert!(same(*l, *r)));
+                                let rhs = v.log_softmax();
+                                lp_lane.iter().zip(rhs.iter()).for_each(|(l, r)| assert!(same(*l, *r)));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    array_logsumexp_tests! { f64_array_logsumexp_impl f64 }
+    array_logsumexp_tests! { f32_array_logsumexp_impl f32 }
+}
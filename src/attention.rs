@@ -0,0 +1,412 @@
+use crate::math::ln;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// An accumulator for the softmax-weighted sum of value vectors, `sum_i(softmax(s)_i * v_i)`,
+/// of a stream of scores, `s_i`, and matching value vectors, `v_i`, of dimension `d`, in a
+/// single pass; i.e. the fused kernel of attention ("flash attention"), which extends the
+/// online normalizer of [`LogSumExp`](crate::LogSumExp) and
+/// [`OnlineLogSumExp`](crate::OnlineLogSumExp) with an output vector, rescaled whenever
+/// the running maximum increases.
+///
+/// The state consists of the running maximum, the sum of exponentials scaled by the
+/// running maximum, and the correspondingly weighted sum of the value vectors. States of
+/// disjoint subsequences may be combined by [`OnlineAttention::merge`], for chunked or
+/// parallel evaluation.
+///
+/// Special values of the scores are handled consistently with
+/// [`Softmax`](crate::Softmax): a score of `-inf` (i.e. a masked entry) contributes
+/// nothing, irrespective of its value vector; if any score is `+inf`, the result is the
+/// mean of the value vectors whose scores are `+inf`; if any score is `nan`, or if there
+/// are no scores other than `-inf`, every element of the result is `nan`.
+///
+/// # Examples
+/// ```
+/// use logsumexp::{OnlineAttention, Softmax};
+///
+/// let scores: Vec<f64> = vec![0.5, -1.0, 2.0];
+/// let values: Vec<Vec<f64>> = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+///
+/// let mut acc = OnlineAttention::<f64>::new(2);
+/// for (s, v) in scores.iter().zip(values.iter()) {
+///     acc.push(*s, v);
+/// }
+/// let out = acc.finish();
+///
+/// let p = scores.softmax();
+/// assert!((out[0] - (p[0] + p[2])).abs() < 1e-15);
+/// assert!((out[1] - (p[1] + p[2])).abs() < 1e-15);
+///
+/// // combine partial results
+/// let mut lhs = OnlineAttention::<f64>::new(2);
+/// lhs.push(scores[0], &values[0]);
+/// let mut rhs = OnlineAttention::<f64>::new(2);
+/// rhs.push(scores[1], &values[1]);
+/// rhs.push(scores[2], &values[2]);
+/// lhs.merge(&rhs);
+/// assert!(lhs.finish().iter().zip(out.iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnlineAttention<T> {
+    max: T,
+    sum: T,
+    out: Vec<T>,
+}
+
+/// A trait for computing softmax-weighted sums of value vectors,
+/// `sum_i(softmax(s)_i * v_i)`, over slices in a single pass, as by [`OnlineAttention`],
+/// for one or several heads, without allocation beyond the result.
+///
+/// # Panics
+/// The methods panic if the lengths of the slices do not match the dimensions.
+pub trait SoftmaxWeightedSum {
+    type Output;
+
+    /// Return the softmax-weighted sum of the rows of `values`, an `n` by `d` row-major
+    /// matrix, with weights given by the softmax of `self`, the `n` scores.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{Softmax, SoftmaxWeightedSum};
+    ///
+    /// let scores: Vec<f64> = vec![0.5, -1.0, f64::NEG_INFINITY, 2.0];
+    /// let values: Vec<f64> = vec![1.0, 0.0, 0.0, 1.0, 5.0, 5.0, 1.0, 1.0];
+    /// let out = scores.softmax_weighted_sum(&values, 2);
+    ///
+    /// let p = scores.softmax();
+    /// assert!((out[0] - (p[0] + p[3])).abs() < 1e-15);
+    /// assert!((out[1] - (p[1] + p[3])).abs() < 1e-15);
+    /// ```
+    fn softmax_weighted_sum(&self, values: &Self, d: usize) -> Self::Output;
+
+    /// Return the softmax-weighted sums for `h` heads, where `self` holds the scores of
+    /// each head, an `h` by `n` row-major matrix, and `values` holds the value vectors of
+    /// each head, `h` row-major `n` by `d` matrices, i.e. `values[(k * n + i) * d + j]`
+    /// is element `j` of the value vector `i` of head `k`. The result is `h` by `d`; with
+    /// no heads, it is empty, and with no keys, each element is `nan`, as the normalizer
+    /// of no scores is zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SoftmaxWeightedSum;
+    ///
+    /// // 2 heads, 3 keys, values of dimension 2
+    /// let scores: Vec<f64> = vec![0.5, -1.0, 2.0, 1.0, 1.0, 1.0];
+    /// let values: Vec<f64> = (0..12).map(|x| x as f64).collect();
+    /// let out = scores.softmax_weighted_sum_heads(&values, 2, 2);
+    /// assert_eq!(out.len(), 4);
+    /// assert_eq!(&out[..2], &scores[..3].softmax_weighted_sum(&values[..6], 2)[..]);
+    /// // equal scores: the mean of the value vectors
+    /// assert!((out[2] - 8.0).abs() < 1e-14 && (out[3] - 9.0).abs() < 1e-14);
+    /// ```
+    fn softmax_weighted_sum_heads(&self, values: &Self, h: usize, d: usize) -> Self::Output;
+}
+
+macro_rules! impl_online_attention {
+    { $($f:ident $mod:ident)+ } => {
+        $(
+            mod $mod {
+                use crate::math::exp;
+
+                // Update the state, `(max, sum, out)`, with a score, `x`, and value, `v`.
+                pub(super) fn push(max: &mut $f, sum: &mut $f, out: &mut [$f], x: $f, v: &[$f]) {
+                    if max.is_nan() || x == $f::NEG_INFINITY {
+                        // nan is absorbing; -inf contributes nothing.
+                    } else if x.is_nan() {
+                        *max = x;
+                    } else if x == *max {
+                        // Includes +inf, for which each term has weight one.
+                        *sum += 1.0;
+                        out.iter_mut().zip(v.iter()).for_each(|(o, v)| *o += v);
+                    } else if x > *max {
+                        // The accumulated terms are rescaled; those of a finite running
+                        // maximum vanish relative to +inf.
+                        let scale = if x == $f::INFINITY { 0.0 } else { exp(*max - x) };
+                        *sum = *sum * scale + 1.0;
+                        out.iter_mut().zip(v.iter()).for_each(|(o, v)| *o = *o * scale + v);
+                        *max = x;
+                    } else if *max != $f::INFINITY {
+                        let w = exp(x - *max);
+                        *sum += w;
+                        out.iter_mut().zip(v.iter()).for_each(|(o, v)| *o += w * v);
+                    }
+                }
+
+                // Combine the state `other` into `(max, sum, out)`.
+                pub(super) fn merge(max: &mut $f, sum: &mut $f, out: &mut [$f], other_max: $f, other_sum: $f, other_out: &[$f]) {
+                    if max.is_nan() || other_max == $f::NEG_INFINITY {
+                        // Nothing to do.
+                    } else if other_max.is_nan() || *max == $f::NEG_INFINITY {
+                        *max = other_max;
+                        *sum = other_sum;
+                        out.copy_from_slice(other_out);
+                    } else if *max == other_max {
+                        *sum += other_sum;
+                        out.iter_mut().zip(other_out.iter()).for_each(|(o, v)| *o += v);
+                    } else {
+                        let m_new = (*max).max(other_max);
+                        let (a, b) = if m_new == $f::INFINITY {
+                            // Only the terms of +inf remain.
+                            if *max == m_new { (1.0, 0.0) } else { (0.0, 1.0) }
+                        } else {
+                            (exp(*max - m_new), exp(other_max - m_new))
+                        };
+                        *sum = *sum * a + other_sum * b;
+                        out.iter_mut().zip(other_out.iter()).for_each(|(o, v)| *o = *o * a + v * b);
+                        *max = m_new;
+                    }
+                }
+
+                // Normalize the output of the state in place.
+                pub(super) fn normalize(max: $f, sum: $f, out: &mut [$f]) {
+                    if max.is_nan() || max == $f::NEG_INFINITY {
+                        out.fill($f::NAN);
+                    } else {
+                        let r = sum.recip();
+                        out.iter_mut().for_each(|o| *o *= r);
+                    }
+                }
+            }
+
+            impl OnlineAttention<$f> {
+                /// Create an empty accumulator for value vectors of dimension `d`.
+                pub fn new(d: usize) -> Self {
+                    Self {
+                        max: $f::NEG_INFINITY,
+                        sum: 0.0,
+                        out: vec![0.0; d],
+                    }
+                }
+
+                /// Return the dimension of the value vectors.
+                pub fn dim(&self) -> usize {
+                    self.out.len()
+                }
+
+                /// Update the state with a score and its value vector.
+                ///
+                /// # Panics
+                /// If the length of `value` is not the dimension of the accumulator.
+                pub fn push(&mut self, score: $f, value: &[$f]) {
+                    assert_eq!(value.len(), self.out.len(), "value must be of dimension d");
+                    $mod::push(&mut self.max, &mut self.sum, &mut self.out, score, value);
+                }
+
+                /// Combine the state of `other` into `self`, such that the result is the
+                /// state which would have been obtained by pushing the scores and values
+                /// of `other` after those of `self`.
+                ///
+                /// # Panics
+                /// If the dimensions of the accumulators differ.
+                pub fn merge(&mut self, other: &Self) {
+                    assert_eq!(other.out.len(), self.out.len(), "dimensions must agree");
+                    $mod::merge(&mut self.max, &mut self.sum, &mut self.out, other.max, other.sum, &other.out);
+                }
+
+                /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the
+                /// scores accumulated thus far, i.e. the log of the softmax normalizer.
+                pub fn ln_sum_exp(&self) -> $f {
+                    if self.max.is_finite() {
+                        self.max + ln(self.sum)
+                    } else {
+                        self.max
+                    }
+                }
+
+                /// Write the softmax-weighted sum of the value vectors accumulated thus
+                /// far to `dest`.
+                ///
+                /// # Panics
+                /// If the length of `dest` is not the dimension of the accumulator.
+                pub fn finish_into(&self, dest: &mut [$f]) {
+                    assert_eq!(dest.len(), self.out.len(), "dest must be of dimension d");
+                    dest.copy_from_slice(&self.out);
+                    $mod::normalize(self.max, self.sum, dest);
+                }
+
+                /// Return the softmax-weighted sum of the value vectors accumulated thus
+                /// far.
+                pub fn finish(&self) -> Vec<$f> {
+                    let mut dest = vec![0.0; self.out.len()];
+                    self.finish_into(&mut dest);
+                    dest
+                }
+            }
+
+            impl SoftmaxWeightedSum for [$f] {
+                type Output = Vec<$f>;
+
+                fn softmax_weighted_sum(&self, values: &Self, d: usize) -> Self::Output {
+                    self.softmax_weighted_sum_heads(values, 1, d)
+                }
+
+                fn softmax_weighted_sum_heads(&self, values: &Self, h: usize, d: usize) -> Self::Output {
+                    if h == 0 {
+                        assert!(self.is_empty(), "scores must be h by n");
+                        assert!(values.is_empty(), "values must be h by n by d");
+                        return Vec::new();
+                    }
+                    assert_eq!(self.len() % h, 0, "scores must be h by n");
+                    let n = self.len() / h;
+                    assert_eq!(values.len(), h * n * d, "values must be h by n by d");
+                    if n == 0 {
+                        // The normalizer of no scores is zero.
+                        return vec![$f::NAN; h * d];
+                    }
+                    let mut out = vec![0.0; h * d];
+                    if d == 0 {
+                        return out;
+                    }
+                    for ((scores, values), out) in self
+                        .chunks_exact(n)
+                        .zip(values.chunks_exact(n * d))
+                        .zip(out.chunks_exact_mut(d))
+                    {
+                        let (mut max, mut sum) = ($f::NEG_INFINITY, 0.0);
+                        for (x, v) in scores.iter().zip(values.chunks_exact(d)) {
+                            $mod::push(&mut max, &mut sum, out, *x, v);
+                        }
+                        $mod::normalize(max, sum, out);
+                    }
+                    out
+                }
+            }
+        )+
+    }
+}
+impl_online_attention! { f64 f64_attention f32 f32_attention }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::{LogSumExp, Softmax};
+
+    macro_rules! online_attention_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                // The 2-pass evaluation, via `Softmax`.
+                fn reference(scores: &[$f], values: &[$f], d: usize) -> Vec<$f> {
+                    let p = scores.softmax();
+                    let mut out = vec![0.0; d];
+                    for (p, v) in p.iter().zip(values.chunks_exact(d)) {
+                        out.iter_mut().zip(v.iter()).for_each(|(o, v)| *o += p * v);
+                    }
+                    out
+                }
+
+                fn assert_close(lhs: &[$f], rhs: &[$f]) {
+                    assert_eq!(lhs.len(), rhs.len());
+                    for (x, y) in lhs.iter().zip(rhs.iter()) {
+                        if y.is_nan() {
+                            assert!(x.is_nan(), "{:?} {:?}", lhs, rhs);
+                        } else {
+                            assert!((x - y).abs() <= 64.0 * $f::EPSILON * y.abs().max(1.0), "{} {}", x, y);
+                        }
+                    }
+                }
+
+                fn online(scores: &[$f], values: &[$f], d: usize) -> OnlineAttention<$f> {
+                    let mut acc = OnlineAttention::<$f>::new(d);
+                    for (s, v) in scores.iter().zip(values.chunks_exact(d)) {
+                        acc.push(*s, v);
+                    }
+                    acc
+                }
+
+                fn cases() -> Vec<Vec<$f>> {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    vec![
+                        vec![0.5],
+                        values::<$f>(100, -20.0, 20.0),
+                        vec![neg_inf, 0.5, neg_inf, 1.0],
+                        vec![0.5, inf, 1.0, inf],
+                        vec![inf, 0.5],
+                        vec![0.5, nan, 1.0],
+                        vec![inf, nan],
+                        vec![neg_inf; 3],
+                    ]
+                }
+
+                #[test]
+                fn online_attention_works() {
+                    let d = 3;
+                    for scores in cases() {
+                        let values = values::<$f>(scores.len() * d, -2.0, 2.0);
+                        let rhs = reference(&scores, &values, d);
+                        let acc = online(&scores, &values, d);
+                        assert_eq!(acc.dim(), d);
+                        assert_close(&acc.finish(), &rhs);
+                        let lse = acc.ln_sum_exp();
+                        let lse_rhs = scores.iter().ln_sum_exp();
+                        assert!(lse == lse_rhs || (lse - lse_rhs).abs() < 16.0 * $f::EPSILON * lse_rhs.abs().max(1.0) || (lse.is_nan() && lse_rhs.is_nan()));
+                        assert_close(&scores.softmax_weighted_sum(&values, d), &rhs);
+                    }
+
+                    let acc = OnlineAttention::<$f>::new(2);
+                    assert!(acc.finish().iter().all(|x| x.is_nan()));
+                    assert_eq!(acc.ln_sum_exp(), $f::NEG_INFINITY);
+                }
+
+                #[test]
+                fn masked_values_do_not_contribute() {
+                    let scores: Vec<$f> = vec![0.5, $f::NEG_INFINITY, 1.0];
+                    let values: Vec<$f> = vec![1.0, $f::NAN, 2.0];
+                    let out = online(&scores, &values, 1).finish();
+                    assert_close(&out, &reference(&[0.5, 1.0], &[1.0, 2.0], 1));
+                }
+
+                #[test]
+                fn merge_works() {
+                    let d = 2;
+                    for scores in cases() {
+                        let values = values::<$f>(scores.len() * d, -2.0, 2.0);
+                        let rhs = reference(&scores, &values, d);
+                        for split in 0..=scores.len() {
+                            let mut lhs = online(&scores[..split], &values[..split * d], d);
+                            let other = online(&scores[split..], &values[split * d..], d);
+                            lhs.merge(&other);
+                            assert_close(&lhs.finish(), &rhs);
+                        }
+                    }
+                }
+
+                #[test]
+                fn heads_works() {
+                    let (h, n, d) = (3, 17, 4);
+                    let scores = values::<$f>(h * n, -10.0, 10.0);
+                    let values = values::<$f>(h * n * d, -1.0, 1.0);
+                    let out = scores.softmax_weighted_sum_heads(&values, h, d);
+                    assert_eq!(out.len(), h * d);
+                    for k in 0..h {
+                        let rhs = reference(&scores[k * n..][..n], &values[k * n * d..][..n * d], d);
+                        assert_close(&out[k * d..][..d], &rhs);
+                    }
+
+                    // no keys
+                    let e: Vec<$f> = vec![];
+                    let out = e.softmax_weighted_sum_heads(&e, 2, 3);
+                    assert_eq!(out.len(), 6);
+                    assert!(out.iter().all(|x| x.is_nan()));
+
+                    // no heads
+                    assert_eq!(e.softmax_weighted_sum_heads(&e, 0, 3), vec![]);
+                }
+
+                #[test]
+                #[should_panic]
+                fn shapes_are_checked() {
+                    let v: Vec<$f> = vec![0.0; 5];
+                    v.softmax_weighted_sum(&v, 2);
+                }
+            }
+        }
+    }
+    online_attention_tests! { f64_online_attention_impl f64 }
+    online_attention_tests! { f32_online_attention_impl f32 }
+}
@@ -0,0 +1,235 @@
+use crate::OnlineLogSumExp;
+use core::fmt;
+
+/// The error returned by [`CheckedLogSumExp`] when the sequence contains a value for
+/// which the LogSumExp is not a meaningful result, or contains no values. The index is
+/// the position of the first such value in the sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSumExpError {
+    /// A `nan` was encountered at `index`, under [`NanPolicy::Error`].
+    Nan { index: usize },
+    /// `+inf` was encountered at `index`.
+    Infinity { index: usize },
+    /// The sequence is empty, or, under [`NanPolicy::Skip`], contains only `nan`.
+    Empty,
+}
+
+impl fmt::Display for LogSumExpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogSumExpError::Nan { index } => write!(f, "log-sum-exp: nan at index {}", index),
+            LogSumExpError::Infinity { index } => {
+                write!(f, "log-sum-exp: +inf at index {}", index)
+            }
+            LogSumExpError::Empty => write!(f, "log-sum-exp: empty sequence"),
+        }
+    }
+}
+
+impl core::error::Error for LogSumExpError {}
+
+/// The treatment of `nan` by [`CheckedLogSumExp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    /// The result is `nan`, as for [`LogSumExp`](crate::LogSumExp).
+    #[default]
+    Propagate,
+    /// `nan` values are excluded from the sum, as by numpy's `nanlogsumexp`.
+    Skip,
+    /// A `nan` is reported as [`LogSumExpError::Nan`].
+    Error,
+}
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// sequence using the 1-pass (online) algorithm of [`LogSumExp`](crate::LogSumExp),
+/// reporting, rather than absorbing, values which indicate problems with the data.
+///
+/// An empty sequence, for which `LogSumExp` returns `-inf`, and `+inf`, which saturates
+/// the result of `LogSumExp`, are reported as errors, whereas `-inf` (i.e. `ln(0)`)
+/// contributes nothing, as usual. The treatment of `nan` is determined by the
+/// [`NanPolicy`]. In each case, the first offending value determines the outcome.
+pub trait CheckedLogSumExp<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the sequence,
+    /// or the error which describes the first offending value.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{CheckedLogSumExp, LogSumExp, LogSumExpError, NanPolicy};
+    ///
+    /// let v: Vec<f64> = vec![0.5, f64::NEG_INFINITY, 1.0];
+    /// assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Error), Ok(v.iter().ln_sum_exp()));
+    ///
+    /// let v: Vec<f64> = vec![0.5, f64::NAN, 1.0, f64::INFINITY];
+    /// assert!(v.iter().ln_sum_exp_checked(NanPolicy::Propagate).unwrap().is_nan());
+    /// assert_eq!(
+    ///     v[..3].iter().ln_sum_exp_checked(NanPolicy::Skip),
+    ///     Ok([0.5, 1.0].iter().ln_sum_exp())
+    /// );
+    /// assert_eq!(
+    ///     v.iter().ln_sum_exp_checked(NanPolicy::Error),
+    ///     Err(LogSumExpError::Nan { index: 1 })
+    /// );
+    /// assert_eq!(
+    ///     v.iter().ln_sum_exp_checked(NanPolicy::Skip),
+    ///     Err(LogSumExpError::Infinity { index: 3 })
+    /// );
+    ///
+    /// let v: Vec<f64> = vec![];
+    /// assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Propagate), Err(LogSumExpError::Empty));
+    /// ```
+    fn ln_sum_exp_checked(self, policy: NanPolicy) -> Self::Output;
+}
+
+macro_rules! impl_checked_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> CheckedLogSumExp<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = Result<$f, LogSumExpError>;
+                fn ln_sum_exp_checked(self, policy: NanPolicy) -> Self::Output {
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    let mut empty = true;
+                    for (index, x) in self.enumerate() {
+                        if x.is_nan() {
+                            match policy {
+                                NanPolicy::Propagate => return Ok(x),
+                                NanPolicy::Skip => continue,
+                                NanPolicy::Error => return Err(LogSumExpError::Nan { index }),
+                            }
+                        } else if x == $f::INFINITY {
+                            return Err(LogSumExpError::Infinity { index });
+                        }
+                        empty = false;
+                        acc.push(x);
+                    }
+                    if empty {
+                        Err(LogSumExpError::Empty)
+                    } else {
+                        Ok(acc.finish())
+                    }
+                }
+            }
+
+            impl<'a, U> CheckedLogSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = Result<$f, LogSumExpError>;
+                fn ln_sum_exp_checked(self, policy: NanPolicy) -> Self::Output {
+                    self.map(|x| *x).ln_sum_exp_checked(policy)
+                }
+            }
+        )+
+    }
+}
+impl_checked_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! checked_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                const POLICIES: [NanPolicy; 3] = [NanPolicy::Propagate, NanPolicy::Skip, NanPolicy::Error];
+
+                #[test]
+                fn ln_sum_exp_checked_works() {
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    let cases: Vec<Vec<$f>> = vec![
+                        vec![x],
+                        vec![x, y],
+                        vec![neg_inf, x, neg_inf, y],
+                        vec![neg_inf],
+                        vec![neg_inf; 3],
+                    ];
+                    for v in cases {
+                        for policy in POLICIES {
+                            let rhs = v.iter().ln_sum_exp();
+                            assert_eq!(v.iter().ln_sum_exp_checked(policy), Ok(rhs));
+                            assert_eq!(v.clone().into_iter().ln_sum_exp_checked(policy), Ok(rhs));
+                        }
+                    }
+
+                    let v: Vec<$f> = vec![];
+                    for policy in POLICIES {
+                        assert_eq!(v.iter().ln_sum_exp_checked(policy), Err(LogSumExpError::Empty));
+                    }
+                }
+
+                #[test]
+                fn ln_sum_exp_checked_special_values() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    // +inf is an error under every policy
+                    let v = vec![x, neg_inf, inf, y];
+                    for policy in POLICIES {
+                        assert_eq!(v.iter().ln_sum_exp_checked(policy), Err(LogSumExpError::Infinity { index: 2 }));
+                    }
+
+                    // nan
+                    let v = vec![x, nan, y, nan];
+                    assert!(v.iter().ln_sum_exp_checked(NanPolicy::Propagate).unwrap().is_nan());
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Skip), Ok([x, y].iter().ln_sum_exp()));
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Error), Err(LogSumExpError::Nan { index: 1 }));
+
+                    // the first offending value determines the outcome
+                    let v = vec![x, nan, inf];
+                    assert!(v.iter().ln_sum_exp_checked(NanPolicy::Propagate).unwrap().is_nan());
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Skip), Err(LogSumExpError::Infinity { index: 2 }));
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Error), Err(LogSumExpError::Nan { index: 1 }));
+                    let v = vec![inf, nan];
+                    for policy in POLICIES {
+                        assert_eq!(v.iter().ln_sum_exp_checked(policy), Err(LogSumExpError::Infinity { index: 0 }));
+                    }
+
+                    // only nan
+                    let v = vec![nan; 3];
+                    assert!(v.iter().ln_sum_exp_checked(NanPolicy::Propagate).unwrap().is_nan());
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Skip), Err(LogSumExpError::Empty));
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Error), Err(LogSumExpError::Nan { index: 0 }));
+
+                    // nan and -inf
+                    let v = vec![nan, neg_inf];
+                    assert_eq!(v.iter().ln_sum_exp_checked(NanPolicy::Skip), Ok(neg_inf));
+                }
+            }
+        }
+    }
+    checked_logsumexp_tests! { f64_checked_logsumexp_impl f64 }
+    checked_logsumexp_tests! { f32_checked_logsumexp_impl f32 }
+
+    #[test]
+    fn display_works() {
+        use alloc::string::ToString;
+        assert_eq!(
+            LogSumExpError::Nan { index: 3 }.to_string(),
+            "log-sum-exp: nan at index 3"
+        );
+        assert_eq!(
+            LogSumExpError::Infinity { index: 0 }.to_string(),
+            "log-sum-exp: +inf at index 0"
+        );
+        assert_eq!(
+            LogSumExpError::Empty.to_string(),
+            "log-sum-exp: empty sequence"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::math::{exp, ln};
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// sequence using the 1-pass (online) algorithm of [`LogSumExp`](crate::LogSumExp), with
+/// the scaled sum accumulated by compensated (Kahan-Babuška-Neumaier) summation. The
+/// handling of +/-infinity and `nan` is identical to that of `LogSumExp`.
+///
+/// The error of the online algorithm grows linearly with the length of the sequence, as
+/// each term incurs a rounding error in the addition to the scaled sum. With compensated
+/// summation, the error of the sum is independent of the length of the sequence, to first
+/// order. The scaled sum (and its compensation) must still be rescaled when the running
+/// maximum increases, which incurs a rounding error each time. Hence, if `exp` and `ln`
+/// are faithfully rounded, the absolute error of the result is, to first order in
+/// `EPSILON`, at most `(k + 3 + ln(n)) * EPSILON + ulp(x) / 2`, where `x` is the result,
+/// `n` is the number of terms and `k` is the number of increases of the running maximum;
+/// for a sequence in random order, `k` is approximately `ln(n)`. The `ln(n)` term is due
+/// to the rounding of the differences `x_i - max`, hence, is inherent to the problem.
+///
+/// If the sequence is a slice which may be traversed twice, [`PairwiseLogSumExp`]
+/// eliminates the rescaling, and with it, the dependence on the order of the sequence.
+pub trait CompensatedLogSumExp<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the sequence,
+    /// using compensated summation.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::CompensatedLogSumExp;
+    ///
+    /// // ln(10^6 * exp(0.1))
+    /// let v: Vec<f32> = vec![0.1; 1_000_000];
+    /// let rhs = 0.1 + 1e6_f32.ln();
+    /// assert!((v.iter().ln_sum_exp_compensated() - rhs).abs() <= 2.0 * f32::EPSILON * rhs);
+    ///
+    /// // handle +/- infinity and `nan`
+    /// let v: Vec<f64> = vec![f64::NEG_INFINITY, 0.5];
+    /// assert_eq!(v.iter().ln_sum_exp_compensated(), 0.5);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5];
+    /// assert_eq!(v.into_iter().ln_sum_exp_compensated(), f64::INFINITY);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5, f64::NAN];
+    /// assert!(v.iter().ln_sum_exp_compensated().is_nan());
+    /// ```
+    fn ln_sum_exp_compensated(self) -> Self::Output;
+}
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// slice in two passes, the first of which determines the maximum, and the second of
+/// which sums the exponentials (relative to the maximum) by pairwise summation. The
+/// handling of +/-infinity and `nan` is identical to that of
+/// [`LogSumExp`](crate::LogSumExp).
+///
+/// The slice is divided recursively in halves, down to blocks of at most 8 elements,
+/// which are summed in order. Hence, the relative error of the sum is at most
+/// `(ceil(log2(n)) + 4) * EPSILON`, to first order, irrespective of the order of the
+/// elements. If `exp` and `ln` are faithfully rounded, the absolute error of the result
+/// is, to first order in `EPSILON`, at most `(ceil(log2(n)) + 5 + ln(n)) * EPSILON +
+/// ulp(x) / 2`, where `x` is the result and `n` is the length of the slice; as for
+/// [`CompensatedLogSumExp`], the `ln(n)` term is inherent to the problem.
+pub trait PairwiseLogSumExp {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the slice,
+    /// using pairwise summation.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::PairwiseLogSumExp;
+    ///
+    /// // ln(10^6 * exp(0.1))
+    /// let v: Vec<f32> = vec![0.1; 1_000_000];
+    /// let rhs = 0.1 + 1e6_f32.ln();
+    /// assert!((v.ln_sum_exp_pairwise() - rhs).abs() <= 2.0 * f32::EPSILON * rhs);
+    ///
+    /// // handle +/- infinity and `nan`
+    /// let v: Vec<f64> = vec![f64::NEG_INFINITY, 0.5];
+    /// assert_eq!(v.ln_sum_exp_pairwise(), 0.5);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5];
+    /// assert_eq!(v.ln_sum_exp_pairwise(), f64::INFINITY);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5, f64::NAN];
+    /// assert!(v.ln_sum_exp_pairwise().is_nan());
+    /// ```
+    fn ln_sum_exp_pairwise(&self) -> Self::Output;
+}
+
+// The length of the blocks which are summed in order by `PairwiseLogSumExp`.
+const PAIRWISE_BLOCK: usize = 8;
+
+macro_rules! impl_compensated_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> CompensatedLogSumExp<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = $f;
+                fn ln_sum_exp_compensated(mut self) -> Self::Output {
+                    let mut max = $f::NEG_INFINITY;
+                    let mut sum: $f = 0.0;
+                    let mut comp: $f = 0.0;
+                    while let Some(x) = self.next() {
+                        if x == $f::NEG_INFINITY {
+                            continue;
+                        } else if x == $f::INFINITY {
+                            for x in self.by_ref() {
+                                if x.is_nan() {
+                                    return x;
+                                }
+                            }
+                            return $f::INFINITY;
+                        } else if x.is_nan() {
+                            return x;
+                        }
+                        if x > max {
+                            let r = exp(max - x);
+                            sum *= r;
+                            comp *= r;
+                            max = x;
+                        }
+                        let t = exp(x - max);
+                        let s = sum + t;
+                        // Both summands are non-negative.
+                        comp += if sum >= t { (sum - s) + t } else { (t - s) + sum };
+                        sum = s;
+                    }
+                    max + ln(sum + comp)
+                }
+            }
+
+            impl<'a, U> CompensatedLogSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = $f;
+                fn ln_sum_exp_compensated(self) -> Self::Output {
+                    self.map(|x| *x).ln_sum_exp_compensated()
+                }
+            }
+
+            impl PairwiseLogSumExp for [$f] {
+                type Output = $f;
+                fn ln_sum_exp_pairwise(&self) -> Self::Output {
+                    fn sum_exp(v: &[$f], max: $f) -> $f {
+                        if v.len() <= PAIRWISE_BLOCK {
+                            v.iter().map(|x| exp(x - max)).sum()
+                        } else {
+                            let (lhs, rhs) = v.split_at(v.len() / 2);
+                            sum_exp(lhs, max) + sum_exp(rhs, max)
+                        }
+                    }
+
+                    let mut max = $f::NEG_INFINITY;
+                    for &x in self {
+                        if x.is_nan() {
+                            return x;
+                        }
+                        max = max.max(x);
+                    }
+                    if max.is_infinite() {
+                        // +inf, or the slice is empty or contains only -inf
+                        return max;
+                    }
+                    max + ln(sum_exp(self, max))
+                }
+            }
+        )+
+    }
+}
+impl_compensated_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::{LogSumExp, PreciseLogSumExp};
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    // The number of increases of the running maximum.
+    fn increases<T: PartialOrd + Copy>(v: &[T]) -> usize {
+        let mut k = 0;
+        let mut max = v[0];
+        for &x in &v[1..] {
+            if x > max {
+                max = x;
+                k += 1;
+            }
+        }
+        k
+    }
+
+    macro_rules! compensated_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn assert_within_bounds(v: &[f64]) {
+                    let v: Vec<$f> = v.iter().map(|&x| x as $f).collect();
+                    let rhs = v.iter().map(|&x| x as f64).ln_sum_exp_precise();
+                    let n = v.len() as $f;
+                    let eps = $f::EPSILON;
+                    // Rounding of the reference to `f64`.
+                    let slack = eps;
+
+                    let lhs = v.iter().ln_sum_exp_compensated();
+                    let k = increases(&v) as $f;
+                    let bound = (k + 3.0 + n.ln()) * eps + eps * lhs.abs() + slack;
+                    let err = (lhs as f64 - rhs).abs();
+                    assert!(err <= bound as f64, "n={} k={} err={} bound={}", n, k, err, bound);
+
+                    let lhs = v.ln_sum_exp_pairwise();
+                    let bound = (n.log2().ceil() + 5.0 + n.ln()) * eps + eps * lhs.abs() + slack;
+                    let err = (lhs as f64 - rhs).abs();
+                    assert!(err <= bound as f64, "n={} err={} bound={}", n, err, bound);
+                }
+
+                #[test]
+                fn error_within_bounds() {
+                    for n in [1, 2, 7, 8, 9, 100, 1000, 12345] {
+                        assert_within_bounds(&values::<f64>(n, -10.0, 10.0));
+                    }
+                    // A long sequence of nearly-equal terms, for which the error of the
+                    // online algorithm grows with the length.
+                    assert_within_bounds(&values::<f64>(1_000_000, 0.0, 1e-3));
+                    // Increasing and decreasing sequences, which maximize and minimize
+                    // the number of rescalings, respectively.
+                    let mut v = values::<f64>(100_000, -5.0, 5.0);
+                    v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    assert_within_bounds(&v);
+                    v.reverse();
+                    assert_within_bounds(&v);
+                }
+
+                #[test]
+                fn more_accurate_than_online() {
+                    let v: Vec<$f> = values::<f64>(1 << 22, 0.0, 1e-3).into_iter().map(|x| x as $f).collect();
+                    let rhs = v.iter().map(|&x| x as f64).ln_sum_exp_precise();
+                    let online = (v.iter().ln_sum_exp() as f64 - rhs).abs();
+                    let compensated = (v.iter().ln_sum_exp_compensated() as f64 - rhs).abs();
+                    let pairwise = (v.ln_sum_exp_pairwise() as f64 - rhs).abs();
+                    assert!(compensated < online, "{} {}", compensated, online);
+                    assert!(pairwise < online, "{} {}", pairwise, online);
+                }
+
+                #[test]
+                fn special_values() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    let cases: Vec<Vec<$f>> = vec![
+                        vec![neg_inf, x, neg_inf, neg_inf],
+                        vec![inf, x, y, neg_inf],
+                        vec![x, inf, y, neg_inf],
+                        vec![neg_inf; 4],
+                        vec![inf; 4],
+                        vec![inf, neg_inf, neg_inf, inf],
+                        vec![],
+                        vec![neg_inf],
+                        vec![inf],
+                        vec![x],
+                        vec![x, y],
+                        vec![x, inf, nan, y],
+                        vec![nan, x, y, inf],
+                        vec![neg_inf, neg_inf, nan],
+                        vec![x, y, nan],
+                        vec![nan],
+                    ];
+                    for v in cases {
+                        let rhs = v.iter().ln_sum_exp();
+                        for lhs in [
+                            v.iter().ln_sum_exp_compensated(),
+                            v.clone().into_iter().ln_sum_exp_compensated(),
+                            v.ln_sum_exp_pairwise(),
+                        ] {
+                            if rhs.is_nan() {
+                                assert!(lhs.is_nan());
+                            } else {
+                                assert!((lhs - rhs).abs() <= 2.0 * $f::EPSILON || lhs == rhs, "{:?} {} {}", v, lhs, rhs);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    compensated_logsumexp_tests! { f64_compensated_logsumexp_impl f64 }
+    compensated_logsumexp_tests! { f32_compensated_logsumexp_impl f32 }
+}
@@ -0,0 +1,483 @@
+use crate::math::exp;
+use crate::OnlineLogSumExp;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The state of the fused cross-entropy of a row of logits with a single target class,
+/// optionally with label smoothing, for a vocabulary which is supplied in chunks, so
+/// that the full row of logits need never be resident; e.g. where each chunk of logits
+/// is computed from a block of the output projection.
+///
+/// The first pass, [`ChunkedCrossEntropy::push`], accumulates the state of
+/// [`OnlineLogSumExp`], the logit of the target and, for label smoothing, the sum of the
+/// logits; states of disjoint chunks may be combined by [`ChunkedCrossEntropy::merge`],
+/// hence, the chunks may be processed in any order, or in parallel. Once every logit has
+/// been pushed, [`ChunkedCrossEntropy::loss`] returns the loss, and the second pass,
+/// [`ChunkedCrossEntropy::grad`], the gradient for each chunk.
+///
+/// With smoothing `eps` and `K` classes, the target distribution is
+/// `q_i = (1 - eps) * [i == target] + eps / K`, the loss is
+/// `ln(sum_i(exp(z_i))) - sum_i(q_i * z_i)`, and the gradient is `softmax(z) - q`.
+/// A logit of `-inf` (i.e. a masked class) has probability zero; if the target is
+/// masked, or, with smoothing, any class is masked, the loss is `inf`. If LogSumExp of
+/// the logits is not finite, i.e. a logit is `+inf` or `nan`, or all are `-inf`, the
+/// loss and gradient are `nan`, as is the loss if the target has not been pushed.
+///
+/// # Examples
+/// ```
+/// use logsumexp::{ChunkedCrossEntropy, CrossEntropy};
+///
+/// let logits: Vec<f64> = (0..1000).map(|i| ((i * 37) % 101) as f64 / 10.0).collect();
+/// let target = 123;
+///
+/// let mut state = ChunkedCrossEntropy::<f64>::new(target, 0.0);
+/// for (c, chunk) in logits.chunks(256).enumerate() {
+///     state.push(c * 256, chunk);
+/// }
+/// let loss = state.loss();
+///
+/// let mut grad = vec![0.0; logits.len()];
+/// for (c, (chunk, g)) in logits.chunks(256).zip(grad.chunks_mut(256)).enumerate() {
+///     state.grad(c * 256, chunk, g);
+/// }
+///
+/// let (rhs_loss, rhs_grad) = logits.cross_entropy(target);
+/// assert!((loss - rhs_loss).abs() < 1e-12);
+/// assert!(grad.iter().zip(rhs_grad.iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkedCrossEntropy<T> {
+    target: usize,
+    smoothing: T,
+    acc: OnlineLogSumExp<T>,
+    target_logit: Option<T>,
+    sum: T,
+    len: usize,
+}
+
+/// A trait for the fused evaluation of the cross-entropy of logits with target classes or
+/// distributions, i.e. the negative log-likelihood under the softmax of the logits,
+/// together with its gradient with respect to the logits, using the 1-pass (online)
+/// algorithm of [`LogSumExp`](crate::LogSumExp) for the normalizer, followed by a single
+/// pass for the gradient; the softmax is never materialized separately. See
+/// [`ChunkedCrossEntropy`] for the treatment of special values, and for vocabularies which
+/// are supplied in chunks.
+///
+/// # Panics
+/// The methods panic if a target is out of range, or the lengths of the slices are
+/// inconsistent.
+pub trait CrossEntropy {
+    type Output;
+
+    /// Return the cross-entropy of the logits with the class `target`,
+    /// `ln(sum_i(exp(z_i))) - z_target`, and its gradient, `softmax(z) - e_target`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{CrossEntropy, LogSumExp, Softmax};
+    ///
+    /// let z: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let (loss, grad) = z.cross_entropy(2);
+    /// assert_eq!(loss, z.iter().ln_sum_exp() - 2.0);
+    /// let mut rhs = z.softmax();
+    /// rhs[2] -= 1.0;
+    /// assert!(grad.iter().zip(rhs.iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+    /// ```
+    fn cross_entropy(&self, target: usize) -> (Self::Output, Vec<Self::Output>);
+
+    /// Return the cross-entropy of the logits with the class `target`, with label
+    /// smoothing `smoothing`, i.e. with the target distribution
+    /// `(1 - smoothing) * e_target + smoothing / K`, and its gradient.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::CrossEntropy;
+    ///
+    /// let z: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let (loss, grad) = z.cross_entropy_smoothed(2, 0.3);
+    /// let q: Vec<f64> = vec![0.1, 0.1, 0.8];
+    /// let (rhs_loss, rhs_grad) = z.cross_entropy_soft(&q);
+    /// assert!((loss - rhs_loss).abs() < 1e-15);
+    /// assert!(grad.iter().zip(rhs_grad.iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+    /// ```
+    fn cross_entropy_smoothed(
+        &self,
+        target: usize,
+        smoothing: Self::Output,
+    ) -> (Self::Output, Vec<Self::Output>);
+
+    /// Return the cross-entropy of the logits with the target distribution `targets`,
+    /// `sum(q) * ln(sum_i(exp(z_i))) - sum_i(q_i * z_i)`, and its gradient,
+    /// `sum(q) * softmax(z) - q`. Terms for which `q_i` is zero are excluded; a negative
+    /// or `nan` `q_i` results in `nan`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::CrossEntropy;
+    ///
+    /// let z: Vec<f64> = vec![0.5, f64::NEG_INFINITY, 2.0];
+    /// let (loss, grad) = z.cross_entropy_soft(&[0.25, 0.0, 0.75]);
+    /// assert!(loss.is_finite());
+    /// assert_eq!(grad[1], 0.0);
+    /// assert!(grad.iter().sum::<f64>().abs() < 1e-15);
+    /// ```
+    fn cross_entropy_soft(&self, targets: &Self) -> (Self::Output, Vec<Self::Output>);
+
+    /// Return the mean cross-entropy of a batch of rows of logits, a row-major matrix of
+    /// one row per element of `targets`, with label smoothing `smoothing` (zero for none),
+    /// and its gradient. Rows for which the target is `ignore_index` are excluded from
+    /// the mean, and their gradient is zero; if every row is excluded, the loss is zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::CrossEntropy;
+    ///
+    /// // 3 examples, 2 classes; the last is ignored
+    /// let z: Vec<f64> = vec![0.5, -1.0, 2.0, 0.0, 1.0, 1.0];
+    /// let (loss, grad) = z.cross_entropy_batch(&[0, 1, usize::MAX], 0.0, Some(usize::MAX));
+    /// let (l0, g0) = z[..2].cross_entropy(0);
+    /// let (l1, _) = z[2..4].cross_entropy(1);
+    /// assert!((loss - (l0 + l1) / 2.0).abs() < 1e-15);
+    /// assert!((grad[0] - g0[0] / 2.0).abs() < 1e-15);
+    /// assert_eq!(&grad[4..], &[0.0, 0.0]);
+    /// ```
+    fn cross_entropy_batch(
+        &self,
+        targets: &[usize],
+        smoothing: Self::Output,
+        ignore_index: Option<usize>,
+    ) -> (Self::Output, Vec<Self::Output>);
+}
+
+macro_rules! impl_cross_entropy {
+    { $($f:ident)+ } => {
+        $(
+            impl ChunkedCrossEntropy<$f> {
+                /// Create the state for the class `target`, with label smoothing
+                /// `smoothing` (zero for none).
+                pub fn new(target: usize, smoothing: $f) -> Self {
+                    Self {
+                        target,
+                        smoothing,
+                        acc: OnlineLogSumExp::<$f>::new(),
+                        target_logit: None,
+                        sum: 0.0,
+                        len: 0,
+                    }
+                }
+
+                /// Update the state with the logits of the classes `offset..offset + chunk.len()`.
+                pub fn push(&mut self, offset: usize, chunk: &[$f]) {
+                    self.acc.extend(chunk);
+                    if (offset..offset + chunk.len()).contains(&self.target) {
+                        self.target_logit = Some(chunk[self.target - offset]);
+                    }
+                    if self.smoothing != 0.0 {
+                        self.sum += chunk.iter().sum::<$f>();
+                    }
+                    self.len += chunk.len();
+                }
+
+                /// Combine the state of `other`, for the same target and smoothing, into
+                /// `self`.
+                pub fn merge(&mut self, other: &Self) {
+                    self.acc.merge(&other.acc);
+                    self.target_logit = self.target_logit.or(other.target_logit);
+                    self.sum += other.sum;
+                    self.len += other.len;
+                }
+
+                /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the
+                /// logits pushed thus far.
+                pub fn ln_sum_exp(&self) -> $f {
+                    self.acc.finish()
+                }
+
+                /// Return the loss, once every logit has been pushed.
+                pub fn loss(&self) -> $f {
+                    let lse = self.acc.finish();
+                    match self.target_logit {
+                        Some(z) if lse.is_finite() => {
+                            let mut loss = lse;
+                            // Skipped at full smoothing, where a masked target would give 0 * -inf.
+                            if self.smoothing != 1.0 {
+                                loss -= (1.0 - self.smoothing) * z;
+                            }
+                            if self.smoothing != 0.0 {
+                                loss -= self.smoothing * self.sum / self.len as $f;
+                            }
+                            loss
+                        }
+                        _ => $f::NAN,
+                    }
+                }
+
+                /// Write the gradient with respect to the logits of the classes
+                /// `offset..offset + chunk.len()` to `grad`, once every logit has been
+                /// pushed.
+                ///
+                /// # Panics
+                /// If the lengths of `chunk` and `grad` differ.
+                pub fn grad(&self, offset: usize, chunk: &[$f], grad: &mut [$f]) {
+                    assert_eq!(grad.len(), chunk.len(), "grad must be of the length of chunk");
+                    let lse = self.acc.finish();
+                    if !lse.is_finite() {
+                        grad.fill($f::NAN);
+                        return;
+                    }
+                    let uniform = if self.smoothing != 0.0 { self.smoothing / self.len as $f } else { 0.0 };
+                    for (g, z) in grad.iter_mut().zip(chunk.iter()) {
+                        *g = exp(z - lse) - uniform;
+                    }
+                    if (offset..offset + chunk.len()).contains(&self.target) {
+                        grad[self.target - offset] -= 1.0 - self.smoothing;
+                    }
+                }
+            }
+
+            impl CrossEntropy for [$f] {
+                type Output = $f;
+
+                fn cross_entropy(&self, target: usize) -> (Self::Output, Vec<Self::Output>) {
+                    self.cross_entropy_smoothed(target, 0.0)
+                }
+
+                fn cross_entropy_smoothed(&self, target: usize, smoothing: $f) -> (Self::Output, Vec<Self::Output>) {
+                    assert!(target < self.len(), "target out of range");
+                    let mut state = ChunkedCrossEntropy::<$f>::new(target, smoothing);
+                    state.push(0, self);
+                    let mut grad = vec![0.0; self.len()];
+                    state.grad(0, self, &mut grad);
+                    (state.loss(), grad)
+                }
+
+                fn cross_entropy_soft(&self, targets: &Self) -> (Self::Output, Vec<Self::Output>) {
+                    assert_eq!(targets.len(), self.len(), "targets must be of length K");
+                    if targets.iter().any(|q| *q < 0.0 || q.is_nan()) {
+                        return ($f::NAN, vec![$f::NAN; self.len()]);
+                    }
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    let (mut mass, mut dot) = (0.0, 0.0);
+                    for (z, q) in self.iter().zip(targets.iter()) {
+                        acc.push(*z);
+                        if *q != 0.0 {
+                            mass += q;
+                            dot += q * z;
+                        }
+                    }
+                    let lse = acc.finish();
+                    if !lse.is_finite() {
+                        return ($f::NAN, vec![$f::NAN; self.len()]);
+                    }
+                    let grad = self
+                        .iter()
+                        .zip(targets.iter())
+                        .map(|(z, q)| mass * exp(z - lse) - q)
+                        .collect();
+                    (mass * lse - dot, grad)
+                }
+
+                fn cross_entropy_batch(
+                    &self,
+                    targets: &[usize],
+                    smoothing: $f,
+                    ignore_index: Option<usize>,
+                ) -> (Self::Output, Vec<Self::Output>) {
+                    let mut grad = vec![0.0; self.len()];
+                    if targets.is_empty() {
+                        assert!(self.is_empty(), "logits must have one row per target");
+                        return (0.0, grad);
+                    }
+                    assert_eq!(self.len() % targets.len(), 0, "logits must have one row per target");
+                    let k = self.len() / targets.len();
+
+                    let mut loss = 0.0;
+                    let mut count = 0;
+                    // Indexed rather than by `chunks_exact`, which panics for rows of zero
+                    // classes, even if every row is ignored.
+                    for (i, &t) in targets.iter().enumerate() {
+                        if Some(t) == ignore_index {
+                            continue;
+                        }
+                        assert!(t < k, "target out of range");
+                        let z = &self[i * k..(i + 1) * k];
+                        let g = &mut grad[i * k..(i + 1) * k];
+                        let mut state = ChunkedCrossEntropy::<$f>::new(t, smoothing);
+                        state.push(0, z);
+                        state.grad(0, z, g);
+                        loss += state.loss();
+                        count += 1;
+                    }
+                    if count > 0 {
+                        let r = (count as $f).recip();
+                        grad.iter_mut().for_each(|g| *g *= r);
+                        loss *= r;
+                    }
+                    (loss, grad)
+                }
+            }
+        )+
+    }
+}
+impl_cross_entropy! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::{LogSumExp, Softmax};
+
+    macro_rules! cross_entropy_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn close(a: &[$f], b: &[$f], tol: $f) -> bool {
+                    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= tol)
+                }
+
+                // The unfused evaluation, for the target distribution `q`.
+                fn reference(z: &[$f], q: &[$f]) -> ($f, Vec<$f>) {
+                    let log_p = z.log_softmax();
+                    let loss = -q.iter().zip(log_p.iter()).filter(|(q, _)| **q != 0.0).map(|(q, l)| q * l).sum::<$f>();
+                    let grad = z.softmax().iter().zip(q.iter()).map(|(p, q)| p - q).collect();
+                    (loss, grad)
+                }
+
+                fn smoothed(k: usize, target: usize, eps: $f) -> Vec<$f> {
+                    let mut q = vec![eps / k as $f; k];
+                    q[target] += 1.0 - eps;
+                    q
+                }
+
+                const TOL: $f = 64.0 * $f::EPSILON;
+
+                #[test]
+                fn cross_entropy_works() {
+                    let z = values::<$f>(50, -10.0, 10.0);
+                    for target in [0, 17, 49] {
+                        let (loss, grad) = z.cross_entropy(target);
+                        assert_eq!(loss, z.iter().ln_sum_exp() - z[target]);
+                        let (rhs_loss, rhs_grad) = reference(&z, &smoothed(50, target, 0.0));
+                        assert!((loss - rhs_loss).abs() <= TOL * rhs_loss.abs().max(1.0));
+                        assert!(close(&grad, &rhs_grad, TOL));
+
+                        let (loss, grad) = z.cross_entropy_smoothed(target, 0.1);
+                        let q = smoothed(50, target, 0.1);
+                        let (rhs_loss, rhs_grad) = reference(&z, &q);
+                        assert!((loss - rhs_loss).abs() <= TOL * rhs_loss.abs().max(1.0));
+                        assert!(close(&grad, &rhs_grad, TOL));
+
+                        let (soft_loss, soft_grad) = z.cross_entropy_soft(&q);
+                        assert!((soft_loss - rhs_loss).abs() <= TOL * rhs_loss.abs().max(1.0));
+                        assert!(close(&soft_grad, &rhs_grad, TOL));
+                    }
+                }
+
+                #[test]
+                fn chunked_works() {
+                    let k = 1000;
+                    let z = values::<$f>(k, -20.0, 20.0);
+                    for &(target, eps) in &[(0, 0.0), (511, 0.1), (999, 0.0)] {
+                        let (rhs_loss, rhs_grad) = z.cross_entropy_smoothed(target, eps);
+                        for chunk in [1, 64, 300, 1000] {
+                            // in reverse order, merging the states of alternate chunks
+                            let mut even = ChunkedCrossEntropy::<$f>::new(target, eps);
+                            let mut odd = ChunkedCrossEntropy::<$f>::new(target, eps);
+                            for (c, zc) in z.chunks(chunk).enumerate().rev() {
+                                if c % 2 == 0 { even.push(c * chunk, zc) } else { odd.push(c * chunk, zc) }
+                            }
+                            even.merge(&odd);
+                            assert!((even.loss() - rhs_loss).abs() <= TOL * rhs_loss.abs().max(1.0));
+                            assert!((even.ln_sum_exp() - z.iter().ln_sum_exp()).abs() <= TOL * 16.0);
+
+                            let mut grad = vec![0.0; k];
+                            for (c, (zc, g)) in z.chunks(chunk).zip(grad.chunks_mut(chunk)).enumerate() {
+                                even.grad(c * chunk, zc, g);
+                            }
+                            assert!(close(&grad, &rhs_grad, TOL));
+                        }
+                    }
+
+                    // the target has not been pushed
+                    let mut state = ChunkedCrossEntropy::<$f>::new(5, 0.0);
+                    state.push(0, &z[..5]);
+                    assert!(state.loss().is_nan());
+                }
+
+                #[test]
+                fn batch_works() {
+                    let (n, k) = (6, 7);
+                    let z = values::<$f>(n * k, -5.0, 5.0);
+                    let ignore = usize::MAX;
+                    let targets = vec![0, 6, ignore, 3, ignore, 1];
+                    for eps in [0.0, 0.2] {
+                        let (loss, grad) = z.cross_entropy_batch(&targets, eps, Some(ignore));
+                        let mut rhs_loss = 0.0;
+                        let mut rhs_grad = vec![0.0; n * k];
+                        for (i, &t) in targets.iter().enumerate() {
+                            if t != ignore {
+                                let (l, g) = z[i * k..][..k].cross_entropy_smoothed(t, eps);
+                                rhs_loss += l / 4.0;
+                                rhs_grad[i * k..][..k].iter_mut().zip(g.iter()).for_each(|(r, g)| *r = g / 4.0);
+                            }
+                        }
+                        assert!((loss - rhs_loss).abs() <= TOL * rhs_loss.abs().max(1.0));
+                        assert!(close(&grad, &rhs_grad, TOL));
+                    }
+
+                    // every row is ignored
+                    let (loss, grad) = z[..k].cross_entropy_batch(&[ignore], 0.0, Some(ignore));
+                    assert_eq!(loss, 0.0);
+                    assert!(grad.iter().all(|g| *g == 0.0));
+                    let e: Vec<$f> = vec![];
+                    assert_eq!(e.cross_entropy_batch(&[], 0.0, None), (0.0, vec![]));
+                    // rows of zero classes, every one of which is ignored
+                    assert_eq!(e.cross_entropy_batch(&[ignore; 3], 0.0, Some(ignore)), (0.0, vec![]));
+                }
+
+                #[test]
+                fn special_values() {
+                    let neg_inf = $f::NEG_INFINITY;
+                    // a masked class
+                    let z: Vec<$f> = vec![0.5, neg_inf, 2.0];
+                    let (loss, grad) = z.cross_entropy(0);
+                    assert!(loss.is_finite());
+                    assert_eq!(grad[1], 0.0);
+                    // a masked target, or smoothing over a masked class
+                    assert_eq!(z.cross_entropy(1).0, $f::INFINITY);
+                    assert_eq!(z.cross_entropy_smoothed(0, 0.1).0, $f::INFINITY);
+                    assert_eq!(z.cross_entropy_smoothed(1, 1.0).0, $f::INFINITY);
+
+                    // non-finite LogSumExp
+                    for z in [vec![0.5, $f::INFINITY], vec![0.5, $f::NAN], vec![neg_inf; 2]] {
+                        let (loss, grad) = z.cross_entropy(0);
+                        assert!(loss.is_nan());
+                        assert!(grad.iter().all(|g| g.is_nan()));
+                        let (loss, grad) = z.cross_entropy_soft(&[0.5, 0.5]);
+                        assert!(loss.is_nan());
+                        assert!(grad.iter().all(|g| g.is_nan()));
+                    }
+
+                    // a negative or nan target probability
+                    let z: Vec<$f> = vec![0.5, 2.0];
+                    for q in [[1.5, -0.5], [$f::NAN, 1.0]] {
+                        let (loss, grad) = z.cross_entropy_soft(&q);
+                        assert!(loss.is_nan());
+                        assert!(grad.iter().all(|g| g.is_nan()));
+                    }
+                }
+
+                #[test]
+                #[should_panic]
+                fn target_is_checked() {
+                    let z: Vec<$f> = vec![0.5, 1.0];
+                    z.cross_entropy(2);
+                }
+            }
+        }
+    }
+    cross_entropy_tests! { f64_cross_entropy_impl f64 }
+    cross_entropy_tests! { f32_cross_entropy_impl f32 }
+}
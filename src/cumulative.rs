@@ -0,0 +1,252 @@
+use crate::OnlineLogSumExp;
+use core::iter::{Copied, FusedIterator, Rev};
+
+/// An iterator which yields the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of
+/// every prefix of the underlying sequence, i.e. `ln(exp(x_0) + ... + exp(x_i))` for each
+/// `i`. This `struct` is created by [`LogCumSumExp::ln_cumsum_exp`] and
+/// [`LogCumSumExp::ln_cumsum_exp_rev`].
+#[derive(Debug, Clone)]
+pub struct LnCumSumExp<I, T> {
+    iter: I,
+    acc: OnlineLogSumExp<T>,
+}
+
+/// A trait for computing the running [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
+/// of a sequence, i.e. the log-domain counterpart of a cumulative sum, as an iterator
+/// adapter. Each item of the adapter is the result of [`OnlineLogSumExp::finish`] after
+/// the corresponding item of the sequence has been pushed, hence, is equal to
+/// [`LogSumExp`](crate::LogSumExp) of the prefix, and the special values are handled
+/// identically: `-inf` contributes nothing, and, once `+inf` or `nan` is encountered, all
+/// subsequent items are `+inf` or `nan`, respectively (`nan` taking precedence).
+pub trait LogCumSumExp<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return an iterator over the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
+    /// of every prefix of the sequence.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogCumSumExp, LogSumExp};
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, f64::NEG_INFINITY, 1.5];
+    /// let c: Vec<f64> = v.iter().ln_cumsum_exp().collect();
+    /// assert_eq!(c.len(), v.len());
+    /// for i in 0..v.len() {
+    ///     assert_eq!(c[i], v[..=i].iter().ln_sum_exp());
+    /// }
+    ///
+    /// // a CDF in log space
+    /// let ln_p: Vec<f64> = vec![0.25_f64.ln(), 0.5_f64.ln(), 0.25_f64.ln()];
+    /// let ln_cdf: Vec<f64> = ln_p.into_iter().ln_cumsum_exp().collect();
+    /// assert!((ln_cdf[1] - 0.75_f64.ln()).abs() < f64::EPSILON);
+    /// assert!(ln_cdf[2].abs() < f64::EPSILON);
+    /// ```
+    fn ln_cumsum_exp(self) -> Self::Output;
+
+    /// Return an iterator over the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
+    /// of every suffix of the sequence, from the last item to the first, i.e. the
+    /// running LogSumExp of the reversed sequence.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogCumSumExp, LogSumExp};
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let c: Vec<f64> = v.iter().ln_cumsum_exp_rev().collect();
+    /// assert_eq!(c[0], 1.5);
+    /// assert_eq!(c[1], v[1..].iter().ln_sum_exp());
+    /// assert_eq!(c[2], v.iter().rev().ln_sum_exp());
+    /// ```
+    fn ln_cumsum_exp_rev(self) -> <Rev<Self> as LogCumSumExp<T, Rev<Self>>>::Output
+    where
+        Self: DoubleEndedIterator<Item = T> + Sized,
+        Rev<Self>: LogCumSumExp<T, Rev<Self>>,
+    {
+        self.rev().ln_cumsum_exp()
+    }
+}
+
+/// A trait for replacing the elements of a slice by the running
+/// [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of its prefixes or suffixes,
+/// without allocation. The results are identical to those of [`LogCumSumExp`].
+pub trait SliceLogCumSumExp {
+    /// Replace each element, `x[i]`, by the LogSumExp of `x[..=i]`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, SliceLogCumSumExp};
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let mut c = v.clone();
+    /// c.ln_cumsum_exp_mut();
+    /// assert_eq!(c[0], 0.5);
+    /// assert_eq!(c[2], v.iter().ln_sum_exp());
+    /// ```
+    fn ln_cumsum_exp_mut(&mut self);
+
+    /// Replace each element, `x[i]`, by the LogSumExp of `x[i..]`, accumulating from the
+    /// last element; this is the log survival function of a log-probability mass
+    /// function.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, SliceLogCumSumExp};
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let mut c = v.clone();
+    /// c.ln_cumsum_exp_rev_mut();
+    /// assert_eq!(c[0], v.iter().rev().ln_sum_exp());
+    /// assert_eq!(c[2], 1.5);
+    /// ```
+    fn ln_cumsum_exp_rev_mut(&mut self);
+}
+
+macro_rules! impl_ln_cumsum_exp {
+    { $($f:ident)+ } => {
+        $(
+            impl<I> Iterator for LnCumSumExp<I, $f>
+            where
+                I: Iterator<Item = $f>,
+            {
+                type Item = $f;
+                fn next(&mut self) -> Option<Self::Item> {
+                    let x = self.iter.next()?;
+                    self.acc.push(x);
+                    Some(self.acc.finish())
+                }
+
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    self.iter.size_hint()
+                }
+            }
+
+            impl<I> ExactSizeIterator for LnCumSumExp<I, $f> where I: ExactSizeIterator<Item = $f> {}
+
+            impl<I> FusedIterator for LnCumSumExp<I, $f> where I: FusedIterator<Item = $f> {}
+
+            impl<U> LogCumSumExp<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = LnCumSumExp<U, $f>;
+                fn ln_cumsum_exp(self) -> Self::Output {
+                    LnCumSumExp {
+                        iter: self,
+                        acc: OnlineLogSumExp::<$f>::new(),
+                    }
+                }
+            }
+
+            impl<'a, U> LogCumSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = LnCumSumExp<Copied<U>, $f>;
+                fn ln_cumsum_exp(self) -> Self::Output {
+                    self.copied().ln_cumsum_exp()
+                }
+            }
+
+            impl SliceLogCumSumExp for [$f] {
+                fn ln_cumsum_exp_mut(&mut self) {
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    for x in self.iter_mut() {
+                        acc.push(*x);
+                        *x = acc.finish();
+                    }
+                }
+
+                fn ln_cumsum_exp_rev_mut(&mut self) {
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    for x in self.iter_mut().rev() {
+                        acc.push(*x);
+                        *x = acc.finish();
+                    }
+                }
+            }
+        )+
+    }
+}
+impl_ln_cumsum_exp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! ln_cumsum_exp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn same(lhs: &[$f], rhs: &[$f]) -> bool {
+                    lhs.len() == rhs.len()
+                        && lhs.iter().zip(rhs.iter()).all(|(a, b)| a == b || (a.is_nan() && b.is_nan()))
+                }
+
+                fn prefixes(v: &[$f]) -> Vec<$f> {
+                    (0..v.len()).map(|i| v[..=i].iter().ln_sum_exp()).collect()
+                }
+
+                fn suffixes(v: &[$f]) -> Vec<$f> {
+                    (0..v.len()).map(|i| v[i..].iter().rev().ln_sum_exp()).collect()
+                }
+
+                #[test]
+                fn ln_cumsum_exp_works() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+
+                    let cases: Vec<Vec<$f>> = vec![
+                        vec![],
+                        vec![0.5],
+                        vec![0.5, 1.0, -3.0, 2.25, 1.0],
+                        vec![neg_inf, 0.5, neg_inf, 1.0],
+                        vec![neg_inf; 3],
+                        vec![0.5, inf, 1.0, nan, inf],
+                        vec![0.5, nan, inf, 1.0],
+                    ];
+                    for v in cases {
+                        let p = prefixes(&v);
+                        let lhs: Vec<$f> = v.iter().ln_cumsum_exp().collect();
+                        assert!(same(&lhs, &p), "{:?}", v);
+                        let lhs: Vec<$f> = v.clone().into_iter().ln_cumsum_exp().collect();
+                        assert!(same(&lhs, &p), "{:?}", v);
+                        let mut lhs = v.clone();
+                        lhs.ln_cumsum_exp_mut();
+                        assert!(same(&lhs, &p), "{:?}", v);
+
+                        let s = suffixes(&v);
+                        let mut lhs: Vec<$f> = v.iter().ln_cumsum_exp_rev().collect();
+                        lhs.reverse();
+                        assert!(same(&lhs, &s), "{:?}", v);
+                        let mut lhs: Vec<$f> = v.clone().into_iter().ln_cumsum_exp_rev().collect();
+                        lhs.reverse();
+                        assert!(same(&lhs, &s), "{:?}", v);
+                        let mut lhs = v.clone();
+                        lhs.ln_cumsum_exp_rev_mut();
+                        assert!(same(&lhs, &s), "{:?}", v);
+                    }
+                }
+
+                #[test]
+                fn ln_cumsum_exp_is_lazy() {
+                    let v: Vec<$f> = vec![0.5, 1.0, 1.5];
+                    let mut it = v.iter().ln_cumsum_exp();
+                    assert_eq!(it.len(), 3);
+                    assert_eq!(it.next(), Some(0.5));
+                    assert_eq!(it.len(), 2);
+                    let mut it = (0..).map(|i| i as $f).ln_cumsum_exp();
+                    assert_eq!(it.next(), Some(0.0));
+                    assert_eq!(it.nth(1), Some([0.0 as $f, 1.0, 2.0].iter().ln_sum_exp()));
+                }
+            }
+        }
+    }
+    ln_cumsum_exp_tests! { f64_ln_cumsum_exp_impl f64 }
+    ln_cumsum_exp_tests! { f32_ln_cumsum_exp_impl f32 }
+}
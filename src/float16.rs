@@ -0,0 +1,224 @@
+//! Implementations of `LogAddExp`, `LogSubExp` and `LogSumExp` for the half-precision
+//! types of the `half` crate. The arguments are widened to `f32`, the computation is
+//! performed (and, for `LogSumExp`, accumulated) in `f32`, and only the result is
+//! rounded to half precision.
+//!
+//! With the `num-traits` feature enabled, the `Float` implementations of the `half` crate
+//! are enabled, and all but iteration by value are provided, with identical results, by
+//! the generic implementations.
+
+use crate::LogSumExp;
+#[cfg(not(feature = "num-traits"))]
+use crate::{LogAddExp, LogSubExp};
+use half::{bf16, f16};
+
+macro_rules! impl_half {
+    { $($f:ident)+ } => {
+        $(
+            #[cfg(not(feature = "num-traits"))]
+            impl LogAddExp for $f {
+                type Output = $f;
+                fn ln_add_exp(&self, rhs: Self) -> Self::Output {
+                    $f::from_f32(self.to_f32().ln_add_exp(rhs.to_f32()))
+                }
+            }
+            #[cfg(not(feature = "num-traits"))]
+            impl LogAddExp<&$f> for $f {
+                type Output = $f;
+                fn ln_add_exp(&self, rhs: &$f) -> Self::Output {
+                    self.ln_add_exp(*rhs)
+                }
+            }
+
+            #[cfg(not(feature = "num-traits"))]
+            impl LogSubExp for $f {
+                type Output = $f;
+                fn ln_sub_exp(&self, rhs: Self) -> Self::Output {
+                    $f::from_f32(self.to_f32().ln_sub_exp(rhs.to_f32()))
+                }
+            }
+            #[cfg(not(feature = "num-traits"))]
+            impl LogSubExp<&$f> for $f {
+                type Output = $f;
+                fn ln_sub_exp(&self, rhs: &$f) -> Self::Output {
+                    self.ln_sub_exp(*rhs)
+                }
+            }
+
+            impl<U> LogSumExp<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = $f;
+                fn ln_sum_exp(self) -> Self::Output {
+                    $f::from_f32(self.map(|x| x.to_f32()).ln_sum_exp())
+                }
+            }
+
+            #[cfg(not(feature = "num-traits"))]
+            impl<'a, U> LogSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = $f;
+                fn ln_sum_exp(self) -> Self::Output {
+                    $f::from_f32(self.map(|x| x.to_f32()).ln_sum_exp())
+                }
+            }
+        )+
+    }
+}
+impl_half! { f16 bf16 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "num-traits")]
+    use crate::{LogAddExp, LogSubExp};
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! half_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn ln_add_exp_works() {
+                    let inf = $f::INFINITY;
+                    let neg_inf = $f::NEG_INFINITY;
+                    let nan = $f::NAN;
+                    let x = $f::from_f32(0.5);
+                    let y = $f::from_f32(1.0);
+
+                    assert_eq!(inf.ln_add_exp(inf), inf);
+                    assert_eq!(neg_inf.ln_add_exp(neg_inf), neg_inf);
+                    assert_eq!(inf.ln_add_exp(neg_inf), inf);
+                    assert_eq!(neg_inf.ln_add_exp(inf), inf);
+                    assert_eq!(inf.ln_add_exp(x), inf);
+                    assert_eq!(neg_inf.ln_add_exp(x), x);
+                    assert_eq!(x.ln_add_exp(inf), inf);
+                    assert_eq!(x.ln_add_exp(neg_inf), x);
+
+                    assert!(nan.ln_add_exp(inf).is_nan());
+                    assert!(nan.ln_add_exp(neg_inf).is_nan());
+                    assert!(inf.ln_add_exp(nan).is_nan());
+                    assert!(neg_inf.ln_add_exp(nan).is_nan());
+                    assert!(nan.ln_add_exp(x).is_nan());
+                    assert!(x.ln_add_exp(nan).is_nan());
+
+                    let z = $f::from_f32(0.5_f32.ln_add_exp(1.0_f32));
+                    assert_eq!(x.ln_add_exp(y), z);
+                    assert_eq!(x.ln_add_exp(&y), z);
+                    assert_eq!((&x).ln_add_exp(y), z);
+                    assert_eq!((&x).ln_add_exp(&y), z);
+                }
+
+                #[test]
+                fn ln_sub_exp_works() {
+                    let inf = $f::INFINITY;
+                    let neg_inf = $f::NEG_INFINITY;
+                    let nan = $f::NAN;
+                    let x = $f::from_f32(0.5);
+                    let y = $f::from_f32(1.0);
+
+                    assert!(inf.ln_sub_exp(inf).is_nan());
+                    assert_eq!(neg_inf.ln_sub_exp(neg_inf), neg_inf);
+                    assert_eq!(inf.ln_sub_exp(neg_inf), inf);
+                    assert_eq!(x.ln_sub_exp(neg_inf), x);
+                    assert_eq!(x.ln_sub_exp(x), neg_inf);
+                    assert!(x.ln_sub_exp(y).is_nan());
+                    assert!(nan.ln_sub_exp(x).is_nan());
+                    assert!(x.ln_sub_exp(nan).is_nan());
+
+                    let z = $f::from_f32(1.0_f32.ln_sub_exp(0.5_f32));
+                    assert_eq!(y.ln_sub_exp(x), z);
+                    assert_eq!(y.ln_sub_exp(&x), z);
+                }
+
+                #[test]
+                fn ln_sum_exp_works() {
+                    let inf = $f::INFINITY;
+                    let neg_inf = $f::NEG_INFINITY;
+                    let nan = $f::NAN;
+                    let x = $f::from_f32(0.5);
+                    let y = $f::from_f32(1.0);
+
+                    let cases: Vec<(Vec<$f>, $f)> = vec![
+                        (vec![neg_inf, x, neg_inf, neg_inf], x),
+                        (vec![inf, x, y, neg_inf], inf),
+                        (vec![x, inf, y, neg_inf], inf),
+                        (vec![neg_inf, inf, x, y], inf),
+                        (vec![neg_inf; 4], neg_inf),
+                        (vec![inf; 4], inf),
+                        (vec![neg_inf, neg_inf, neg_inf, inf], inf),
+                        (vec![inf, neg_inf, neg_inf, neg_inf], inf),
+                        (vec![inf, inf, y, neg_inf], inf),
+                        (vec![inf, neg_inf, neg_inf, inf], inf),
+                        (vec![neg_inf, neg_inf, neg_inf, x], x),
+                        (vec![x, neg_inf, neg_inf, neg_inf], x),
+                        (vec![], neg_inf),
+                        (vec![neg_inf], neg_inf),
+                        (vec![inf], inf),
+                        (vec![x], x),
+                        (vec![$f::ZERO], $f::ZERO),
+                        (vec![x, inf], inf),
+                        (vec![x, neg_inf], x),
+                    ];
+                    for (v, rhs) in cases {
+                        assert_eq!(v.iter().ln_sum_exp(), rhs);
+                        assert_eq!(v.into_iter().ln_sum_exp(), rhs);
+                    }
+
+                    let v = vec![neg_inf, x, y, neg_inf];
+                    let rhs = $f::from_f32([0.5_f32, 1.0].iter().ln_sum_exp());
+                    assert_eq!(v.iter().ln_sum_exp(), rhs);
+                    assert_eq!(v.into_iter().ln_sum_exp(), rhs);
+
+                    // Cases involving nan
+                    let cases: Vec<Vec<$f>> = vec![
+                        vec![x, inf, nan, y],
+                        vec![nan, x, y, inf],
+                        vec![inf, inf, x, nan],
+                        vec![inf, inf, neg_inf, nan],
+                        vec![inf, inf, inf, nan],
+                        vec![neg_inf, neg_inf, neg_inf, nan],
+                        vec![neg_inf, neg_inf, inf, nan],
+                        vec![x, y, nan, inf],
+                        vec![x, y, nan],
+                        vec![x, nan, y],
+                        vec![nan, x, y],
+                        vec![nan],
+                        vec![nan; 4],
+                        vec![neg_inf, nan],
+                        vec![inf, nan],
+                        vec![neg_inf, inf, nan],
+                        vec![inf, neg_inf, nan],
+                    ];
+                    for v in cases {
+                        assert!(v.iter().ln_sum_exp().is_nan());
+                        assert!(v.into_iter().ln_sum_exp().is_nan());
+                    }
+                }
+
+                #[test]
+                fn ln_sum_exp_accumulates_in_f32() {
+                    // The sum of 4096 terms of exp(0) is exact in f32, but would
+                    // saturate the mantissa of a half-precision accumulator.
+                    let v = vec![$f::ZERO; 4096];
+                    let rhs = $f::from_f32(4096.0_f32.ln());
+                    assert_eq!(v.iter().ln_sum_exp(), rhs);
+
+                    // Values whose exponentials overflow half precision.
+                    let x = $f::from_f32(12.0);
+                    let v = vec![x, x];
+                    let rhs = $f::from_f32(12.0 + 2.0_f32.ln());
+                    assert_eq!(v.iter().ln_sum_exp(), rhs);
+                }
+            }
+        }
+    }
+    half_tests! { f16_half_impl f16 }
+    half_tests! { bf16_half_impl bf16 }
+}
@@ -0,0 +1,201 @@
+//! Blanket implementations of `LogAddExp`, `LogSubExp` and `LogSumExp` for any type
+//! which implements `num_traits::Float`. These replace the implementations for the
+//! concrete types `f64` and `f32` (and those of the `half` feature), with identical
+//! results; the functions `ln_1p_exp` and `ln_1m_exp` follow the case analysis of
+//! Maechler (2012), restricted to the arguments which arise here. Types with less
+//! precision than `f32`, such as those of the `half` crate, are evaluated in `f32`,
+//! as the sum of many terms would otherwise be limited by their precision.
+//!
+//! Coherence does not permit blanket implementations of `LogSumExp` for both `T` and
+//! `&T`, as a downstream crate could implement `Float` for a reference. Hence, the
+//! blanket implementation is that which iterates by reference, while iteration by value
+//! retains the implementations for the concrete types.
+
+use crate::{LogAddExp, LogSubExp, LogSumExp};
+use num_traits::Float;
+
+// Whether `T` is evaluated in `f32`: its precision is less than that of `f32`,
+// while its range is not greater.
+#[inline]
+fn is_narrow<T: Float>() -> bool {
+    T::epsilon().to_f32().is_some_and(|eps| eps > f32::EPSILON)
+        && T::max_value().to_f32().is_some_and(f32::is_finite)
+}
+
+#[inline]
+fn to_f32<T: Float>(x: T) -> f32 {
+    x.to_f32().unwrap_or(f32::NAN)
+}
+
+#[inline]
+fn from_f32<T: Float>(x: f32) -> T {
+    T::from(x).unwrap_or_else(T::nan)
+}
+
+// `ln(1 + exp(x))` for `x <= 0`.
+#[inline]
+fn ln_1p_exp<T: Float>(x: T) -> T {
+    x.exp().ln_1p()
+}
+
+// `ln(1 - exp(x))` for `x <= 0`.
+#[inline]
+fn ln_1m_exp<T: Float>(x: T) -> T {
+    let ln_2 = (T::one() + T::one()).ln();
+    if x < -ln_2 {
+        (-x.exp()).ln_1p()
+    } else {
+        (-x.exp_m1()).ln()
+    }
+}
+
+impl<T: Float> LogAddExp for T {
+    type Output = T;
+    fn ln_add_exp(&self, rhs: Self) -> Self::Output {
+        if is_narrow::<T>() {
+            return from_f32(to_f32(*self).ln_add_exp(to_f32(rhs)));
+        }
+        let (max, diff) = if *self < rhs {
+            (rhs, *self - rhs)
+        } else if *self == rhs {
+            (rhs, T::zero())
+        } else {
+            (*self, rhs - *self)
+        };
+        max + ln_1p_exp(diff)
+    }
+}
+impl<T: Float> LogAddExp<&T> for T {
+    type Output = T;
+    fn ln_add_exp(&self, rhs: &T) -> Self::Output {
+        self.ln_add_exp(*rhs)
+    }
+}
+
+impl<T: Float> LogSubExp for T {
+    type Output = T;
+    fn ln_sub_exp(&self, rhs: Self) -> Self::Output {
+        if is_narrow::<T>() {
+            from_f32(to_f32(*self).ln_sub_exp(to_f32(rhs)))
+        } else if *self == rhs {
+            if rhs == T::infinity() {
+                T::nan()
+            } else {
+                T::neg_infinity()
+            }
+        } else {
+            // rhs > self yields nan from `ln_1m_exp`
+            *self + ln_1m_exp(rhs - *self)
+        }
+    }
+}
+impl<T: Float> LogSubExp<&T> for T {
+    type Output = T;
+    fn ln_sub_exp(&self, rhs: &T) -> Self::Output {
+        self.ln_sub_exp(*rhs)
+    }
+}
+
+impl<'a, T, U> LogSumExp<&'a T, U> for U
+where
+    T: Float + 'a,
+    U: Iterator<Item = &'a T>,
+{
+    type Output = T;
+    fn ln_sum_exp(mut self) -> Self::Output {
+        if is_narrow::<T>() {
+            return from_f32(self.map(|x| to_f32(*x)).ln_sum_exp());
+        }
+        let mut m_old = T::neg_infinity();
+        let mut sum = T::zero();
+        while let Some(v_i) = self.next() {
+            if *v_i == T::neg_infinity() {
+                continue;
+            } else if *v_i == T::infinity() {
+                for v_i in self.by_ref() {
+                    if v_i.is_nan() {
+                        return *v_i;
+                    }
+                }
+                return T::infinity();
+            } else if v_i.is_nan() {
+                return *v_i;
+            } else {
+                let m_new = m_old.max(*v_i);
+                sum = sum * (m_old - m_new).exp() + (*v_i - m_new).exp();
+                m_old = m_new;
+            }
+        }
+        m_old + sum.ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    // Generic code requires no bounds beyond `Float`.
+    fn ln_mean_exp<T: Float>(v: &[T]) -> T {
+        v.iter().ln_sum_exp() - T::from(v.len()).unwrap().ln()
+    }
+
+    fn ln_add_sub<T: Float>(x: T, y: T) -> (T, T) {
+        (x.ln_add_exp(&y), x.ln_sub_exp(y))
+    }
+
+    macro_rules! generic_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn generic_functions_work() {
+                    let v: Vec<$f> = vec![0.1, 0.2, 0.3, 0.4, 0.5];
+                    let ln_v: Vec<$f> = v.iter().map(|x| x.ln()).collect();
+                    assert!((ln_mean_exp(&ln_v).exp() - 0.3).abs() < 2.0 * $f::EPSILON);
+
+                    let x: $f = 1.0;
+                    let y: $f = 0.5;
+                    let (add, sub) = ln_add_sub(x, y);
+                    assert!((add - (x.exp() + y.exp()).ln()).abs() < 2.0 * $f::EPSILON);
+                    assert!((sub - (x.exp() - y.exp()).ln()).abs() < 4.0 * $f::EPSILON);
+
+                    let inf: $f = $f::INFINITY;
+                    let (add, sub) = ln_add_sub(inf, inf);
+                    assert_eq!(add, inf);
+                    assert!(sub.is_nan());
+                    assert!(ln_mean_exp::<$f>(&[]).is_nan());
+                }
+
+                #[test]
+                fn is_narrow_works() {
+                    assert!(!is_narrow::<$f>());
+                }
+
+                #[test]
+                fn ln_1p_exp_works() {
+                    for x in [0.0, -1e-20, -0.5, -1.0, -20.0, -40.0, -800.0, $f::NEG_INFINITY] {
+                        let x: $f = x;
+                        assert_eq!(ln_1p_exp(x), x.exp().ln_1p());
+                    }
+                }
+
+                #[test]
+                fn ln_1m_exp_works() {
+                    let d: $f = (2.0 as $f).powi(-20);
+                    assert!((ln_1m_exp(-d) - d.ln()).abs() < 1e-5);
+                    assert_eq!(ln_1m_exp($f::NEG_INFINITY), 0.0);
+                    assert_eq!(ln_1m_exp(0.0), $f::NEG_INFINITY);
+                    assert!(ln_1m_exp(1.0).is_nan());
+                    let x: $f = -2.0;
+                    assert!((ln_1m_exp(x) - (1.0 - x.exp()).ln()).abs() < $f::EPSILON);
+                }
+            }
+        }
+    }
+    generic_tests! { f64_generic_impl f64 }
+    generic_tests! { f32_generic_impl f32 }
+}
@@ -0,0 +1,256 @@
+use crate::math::exp;
+use crate::{LogSumExp, Softmax};
+use alloc::vec::Vec;
+
+/// A trait for the derivatives of [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
+/// and of the [softmax](https://en.wikipedia.org/wiki/Softmax_function) of a slice, for
+/// backpropagation through these reductions.
+///
+/// The gradient of LogSumExp is the softmax, `p`. The Jacobian of the softmax,
+/// `diag(p) - p p^T`, is symmetric, hence, its Jacobian-vector product (JVP) and
+/// vector-Jacobian product (VJP) coincide, `p * (v - <p, v>)`; the Jacobian of the log
+/// softmax is `I - 1 p^T`, with JVP `v - <p, v>` and VJP `v - p * sum(v)`. The products
+/// are evaluated without forming the Jacobian, i.e. in `O(n)`, from the inputs, `self`,
+/// of LogSumExp or the softmax.
+///
+/// The softmax is that of [`Softmax`], hence, special values are handled identically;
+/// in particular, if any element is `nan`, or all elements are `-inf`, the derivatives
+/// are `nan`.
+///
+/// # Panics
+/// The products panic if the length of the vector differs from that of `self`.
+pub trait LogSumExpGrad {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the slice and
+    /// its gradient, the softmax.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, LogSumExpGrad, Softmax};
+    ///
+    /// let x: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let (lse, grad) = x.ln_sum_exp_with_grad();
+    /// assert_eq!(lse, x.iter().ln_sum_exp());
+    /// assert!(grad.iter().zip(x.softmax().iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+    /// ```
+    fn ln_sum_exp_with_grad(&self) -> (Self::Output, Vec<Self::Output>);
+
+    /// Return the product of the Jacobian of the softmax at `self` with `tangent`, i.e.
+    /// the directional derivative of the softmax.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExpGrad, Softmax};
+    ///
+    /// let x: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let t: Vec<f64> = vec![1.0, 0.0, -1.0];
+    /// let jvp = x.softmax_jvp(&t);
+    ///
+    /// // compare to a central difference
+    /// let h = 1e-6;
+    /// let xp: Vec<f64> = x.iter().zip(t.iter()).map(|(x, t)| x + h * t).collect();
+    /// let xm: Vec<f64> = x.iter().zip(t.iter()).map(|(x, t)| x - h * t).collect();
+    /// let (pp, pm) = (xp.softmax(), xm.softmax());
+    /// for i in 0..3 {
+    ///     assert!((jvp[i] - (pp[i] - pm[i]) / (2.0 * h)).abs() < 1e-9);
+    /// }
+    /// ```
+    fn softmax_jvp(&self, tangent: &Self) -> Vec<Self::Output>;
+
+    /// Return the product of `cotangent` with the Jacobian of the softmax at `self`, i.e.
+    /// the gradient with respect to `self` of `<cotangent, softmax(self)>`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::LogSumExpGrad;
+    ///
+    /// let x: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let g: Vec<f64> = vec![1.0, 0.0, -1.0];
+    /// // the Jacobian of the softmax is symmetric
+    /// assert_eq!(x.softmax_vjp(&g), x.softmax_jvp(&g));
+    /// // the softmax sums to one, hence, a constant cotangent has no effect
+    /// assert!(x.softmax_vjp(&[2.0, 2.0, 2.0]).iter().all(|v| v.abs() < 1e-15));
+    /// ```
+    fn softmax_vjp(&self, cotangent: &Self) -> Vec<Self::Output>;
+
+    /// Return the product of the Jacobian of the log softmax at `self` with `tangent`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::LogSumExpGrad;
+    ///
+    /// let x: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// // the log softmax is invariant to a shift of its inputs
+    /// assert!(x.log_softmax_jvp(&[1.0, 1.0, 1.0]).iter().all(|v| v.abs() < 1e-15));
+    /// ```
+    fn log_softmax_jvp(&self, tangent: &Self) -> Vec<Self::Output>;
+
+    /// Return the product of `cotangent` with the Jacobian of the log softmax at `self`,
+    /// i.e. the gradient with respect to `self` of `<cotangent, log_softmax(self)>`; e.g.
+    /// for the negative log-likelihood of the class `k`, the cotangent is `-e_k`, and the
+    /// gradient is `p - e_k`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExpGrad, Softmax};
+    ///
+    /// let x: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// let grad = x.log_softmax_vjp(&[0.0, -1.0, 0.0]);
+    /// let mut rhs = x.softmax();
+    /// rhs[1] -= 1.0;
+    /// assert!(grad.iter().zip(rhs.iter()).all(|(a, b)| (a - b).abs() < 1e-15));
+    /// ```
+    fn log_softmax_vjp(&self, cotangent: &Self) -> Vec<Self::Output>;
+}
+
+macro_rules! impl_logsumexp_grad {
+    { $($f:ident)+ } => {
+        $(
+            impl LogSumExpGrad for [$f] {
+                type Output = $f;
+
+                fn ln_sum_exp_with_grad(&self) -> (Self::Output, Vec<Self::Output>) {
+                    let lse = self.iter().ln_sum_exp();
+                    let grad = if lse.is_finite() {
+                        self.iter().map(|x| exp(x - lse)).collect()
+                    } else {
+                        self.softmax()
+                    };
+                    (lse, grad)
+                }
+
+                fn softmax_jvp(&self, tangent: &Self) -> Vec<Self::Output> {
+                    assert_eq!(tangent.len(), self.len(), "tangent must be of length n");
+                    let mut p = self.softmax();
+                    let dot: $f = p.iter().zip(tangent.iter()).map(|(p, t)| p * t).sum();
+                    p.iter_mut().zip(tangent.iter()).for_each(|(p, t)| *p *= t - dot);
+                    p
+                }
+
+                fn softmax_vjp(&self, cotangent: &Self) -> Vec<Self::Output> {
+                    self.softmax_jvp(cotangent)
+                }
+
+                fn log_softmax_jvp(&self, tangent: &Self) -> Vec<Self::Output> {
+                    assert_eq!(tangent.len(), self.len(), "tangent must be of length n");
+                    let p = self.softmax();
+                    let dot: $f = p.iter().zip(tangent.iter()).map(|(p, t)| p * t).sum();
+                    tangent.iter().map(|t| t - dot).collect()
+                }
+
+                fn log_softmax_vjp(&self, cotangent: &Self) -> Vec<Self::Output> {
+                    assert_eq!(cotangent.len(), self.len(), "cotangent must be of length n");
+                    let mut p = self.softmax();
+                    let sum: $f = cotangent.iter().sum();
+                    p.iter_mut().zip(cotangent.iter()).for_each(|(p, g)| *p = g - *p * sum);
+                    p
+                }
+            }
+        )+
+    }
+}
+impl_logsumexp_grad! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! logsumexp_grad_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                const X: [$f; 5] = [0.5, -1.0, 2.0, 0.0, 1.25];
+                const V: [$f; 5] = [1.0, -0.5, 0.25, 2.0, -1.0];
+
+                fn close(a: &[$f], b: &[$f], tol: $f) -> bool {
+                    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= tol)
+                }
+
+                // The central difference of `f` at `X` in the direction `V`.
+                fn directional(f: impl Fn(&[$f]) -> Vec<$f>) -> Vec<$f> {
+                    let h: $f = if $f::EPSILON > 1e-10 { 1e-2 } else { 1e-5 };
+                    let xp: Vec<$f> = X.iter().zip(V.iter()).map(|(x, v)| x + h * v).collect();
+                    let xm: Vec<$f> = X.iter().zip(V.iter()).map(|(x, v)| x - h * v).collect();
+                    f(&xp).iter().zip(f(&xm).iter()).map(|(p, m)| (p - m) / (2.0 * h)).collect()
+                }
+
+                fn tol() -> $f {
+                    if $f::EPSILON > 1e-10 { 1e-3 } else { 1e-8 }
+                }
+
+                #[test]
+                fn ln_sum_exp_with_grad_works() {
+                    let (lse, grad) = X.ln_sum_exp_with_grad();
+                    assert_eq!(lse, X.iter().ln_sum_exp());
+                    assert!(close(&grad, &X.softmax(), 4.0 * $f::EPSILON));
+                    // the gradient agrees with a central difference of LogSumExp
+                    let fd = directional(|x| vec![x.iter().ln_sum_exp()]);
+                    let dot: $f = grad.iter().zip(V.iter()).map(|(g, v)| g * v).sum();
+                    assert!((fd[0] - dot).abs() <= tol());
+                }
+
+                #[test]
+                fn jvp_works() {
+                    assert!(close(&X.softmax_jvp(&V), &directional(|x| x.softmax()), tol()));
+                    assert!(close(&X.log_softmax_jvp(&V), &directional(|x| x.log_softmax()), tol()));
+                }
+
+                #[test]
+                fn vjp_works() {
+                    // <g, J v> == <J^T g, v>
+                    let g: [$f; 5] = [0.25, 1.0, -2.0, 0.5, 0.0];
+                    let dot = |a: &[$f], b: &[$f]| -> $f { a.iter().zip(b.iter()).map(|(a, b)| a * b).sum() };
+                    let lhs = dot(&g, &X.softmax_jvp(&V));
+                    let rhs = dot(&X.softmax_vjp(&g), &V);
+                    assert!((lhs - rhs).abs() <= 8.0 * $f::EPSILON);
+                    let lhs = dot(&g, &X.log_softmax_jvp(&V));
+                    let rhs = dot(&X.log_softmax_vjp(&g), &V);
+                    assert!((lhs - rhs).abs() <= 16.0 * $f::EPSILON);
+                }
+
+                #[test]
+                fn special_values() {
+                    let inf = $f::INFINITY;
+                    let neg_inf = $f::NEG_INFINITY;
+
+                    // -inf has zero gradient
+                    let x: Vec<$f> = vec![0.5, neg_inf, 1.0];
+                    let (lse, grad) = x.ln_sum_exp_with_grad();
+                    assert_eq!(lse, x.iter().ln_sum_exp());
+                    assert_eq!(grad[1], 0.0);
+                    assert_eq!(x.softmax_vjp(&[1.0, 5.0, 0.0])[1], 0.0);
+
+                    // +inf takes all the gradient
+                    let x: Vec<$f> = vec![0.5, inf, 1.0, inf];
+                    let (lse, grad) = x.ln_sum_exp_with_grad();
+                    assert_eq!(lse, inf);
+                    assert_eq!(grad, vec![0.0, 0.5, 0.0, 0.5]);
+
+                    // nan, all -inf, and empty
+                    let x: Vec<$f> = vec![0.5, $f::NAN];
+                    assert!(x.ln_sum_exp_with_grad().1.iter().all(|g| g.is_nan()));
+                    assert!(x.log_softmax_vjp(&[1.0, 0.0]).iter().all(|g| g.is_nan()));
+                    let x: Vec<$f> = vec![neg_inf; 2];
+                    assert_eq!(x.ln_sum_exp_with_grad().0, neg_inf);
+                    assert!(x.softmax_jvp(&[1.0, 0.0]).iter().all(|g| g.is_nan()));
+                    let x: Vec<$f> = vec![];
+                    assert_eq!(x.ln_sum_exp_with_grad(), (neg_inf, vec![]));
+                    assert_eq!(x.softmax_vjp(&[]), vec![]);
+                }
+
+                #[test]
+                #[should_panic]
+                fn lengths_are_checked() {
+                    X.softmax_vjp(&V[1..]);
+                }
+            }
+        }
+    }
+    logsumexp_grad_tests! { f64_logsumexp_grad_impl f64 }
+    logsumexp_grad_tests! { f32_logsumexp_grad_impl f32 }
+}
@@ -0,0 +1,443 @@
+use crate::{LogSemiring, LogSumExp, MaxPlus, Semiring};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A [hidden Markov model](https://en.wikipedia.org/wiki/Hidden_Markov_model) with `n`
+/// states, parameterized by log probabilities, for inference by the forward-backward
+/// algorithm and decoding by the Viterbi algorithm, entirely in log space.
+///
+/// The model holds the log initial state probabilities, `ln_init[i] = ln P(s_0 = i)`,
+/// and the log transition matrix, in row-major order,
+/// `ln_trans[i * n + j] = ln P(s_{t+1} = j | s_t = i)`. The observations enter each
+/// method as a log emission matrix of `T` steps, in row-major order,
+/// `ln_emit[t * n + j] = ln P(o_t | s_t = j)`, hence, any emission model may be used.
+/// The results which are matrices are in the same layout, i.e. `T` rows of `n` columns.
+///
+/// The sums over states are evaluated by [`LogSumExp`], via the [`LogSemiring`];
+/// [`Hmm::forward_in`] and [`Hmm::backward_in`] evaluate the same recursions in any
+/// [`Semiring`]. An impossible event has log probability `-inf`; if the observations are
+/// impossible under the model, i.e. the log-likelihood is `-inf`, the posteriors are
+/// `nan`. An empty sequence of observations has log-likelihood `0`.
+///
+/// # Examples
+/// ```
+/// use logsumexp::Hmm;
+///
+/// // A fair and a loaded coin, which are rarely exchanged.
+/// let ln_init: Vec<f64> = [0.5_f64, 0.5].iter().map(|p| p.ln()).collect();
+/// let ln_trans: Vec<f64> = [0.9_f64, 0.1, 0.1, 0.9].iter().map(|p| p.ln()).collect();
+/// let hmm = Hmm::new(&ln_init, &ln_trans);
+///
+/// // P(heads) is 0.5 for the fair coin and 0.9 for the loaded coin.
+/// let flips = [true, true, true, false, true, true];
+/// let ln_emit: Vec<f64> = flips
+///     .iter()
+///     .flat_map(|&h| if h { [0.5_f64, 0.9] } else { [0.5, 0.1] })
+///     .map(|p| p.ln())
+///     .collect();
+///
+/// let ll = hmm.ln_likelihood(&ln_emit);
+/// assert!(ll < 0.0);
+///
+/// let posteriors = hmm.posteriors(&ln_emit);
+/// assert_eq!(posteriors.ln_likelihood, ll);
+/// // the posterior state marginals sum to one at each step
+/// for row in posteriors.ln_state.chunks(2) {
+///     assert!((row[0].exp() + row[1].exp() - 1.0).abs() < 1e-12);
+/// }
+///
+/// let (score, path) = hmm.viterbi(&ln_emit);
+/// assert_eq!(path, vec![1, 1, 1, 1, 1, 1]);
+/// assert!(score <= ll);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Hmm<'a, T> {
+    ln_init: &'a [T],
+    ln_trans: &'a [T],
+}
+
+/// The posterior distributions of the states of an [`Hmm`], given the observations, as
+/// computed by [`Hmm::posteriors`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posteriors<T> {
+    /// The log-likelihood of the observations, `ln P(o_0, ..., o_{T-1})`.
+    pub ln_likelihood: T,
+    /// The log posterior state marginals, `ln P(s_t = i | o)`, in `T` rows of `n`.
+    pub ln_state: Vec<T>,
+    /// The log posterior transition probabilities, `ln P(s_t = i, s_{t+1} = j | o)`, in
+    /// `T - 1` row-major `n` by `n` matrices, i.e. at `(t * n + i) * n + j`.
+    pub ln_transition: Vec<T>,
+}
+
+impl<'a, T> Hmm<'a, T> {
+    /// Create a model from the log initial state probabilities and the
+    /// row-major log transition matrix.
+    ///
+    /// # Panics
+    /// If `ln_init` is empty, or if the length of `ln_trans` is not the square of the
+    /// length of `ln_init`.
+    pub fn new(ln_init: &'a [T], ln_trans: &'a [T]) -> Self {
+        let n = ln_init.len();
+        assert!(n > 0, "model must have at least one state");
+        assert_eq!(ln_trans.len(), n * n, "transition matrix must be n by n");
+        Self { ln_init, ln_trans }
+    }
+
+    /// Return the number of states.
+    pub fn n_states(&self) -> usize {
+        self.ln_init.len()
+    }
+}
+
+macro_rules! impl_hmm {
+    { $($f:ident)+ } => {
+        $(
+            impl<'a> Hmm<'a, $f> {
+                // The number of steps of the log emission matrix.
+                fn steps(&self, ln_emit: &[$f]) -> usize {
+                    let n = self.n_states();
+                    assert!(ln_emit.len() % n == 0, "emission matrix must have n columns");
+                    ln_emit.len() / n
+                }
+
+                /// Return the forward recursion in the semiring `S`,
+                /// `a[0][j] = init[j] * emit[0][j]` and
+                /// `a[t][j] = emit[t][j] * sum_i(a[t-1][i] * trans[i][j])`,
+                /// where `*` and `sum` are those of `S`. In the [`LogSemiring`], these are
+                /// the log forward variables, `ln P(o_0, ..., o_t, s_t = j)`; in
+                /// [`MaxPlus`], the log probabilities of the most probable paths ending in
+                /// each state.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn forward_in<S: Semiring<$f>>(&self, ln_emit: &[$f]) -> Vec<$f> {
+                    let n = self.n_states();
+                    let steps = self.steps(ln_emit);
+                    let mut alpha = vec![S::zero(); steps * n];
+                    if steps == 0 {
+                        return alpha;
+                    }
+                    for j in 0..n {
+                        alpha[j] = S::mul(self.ln_init[j], ln_emit[j]);
+                    }
+                    for t in 1..steps {
+                        let (prev, next) = alpha.split_at_mut(t * n);
+                        let prev = &prev[(t - 1) * n..];
+                        for (j, a) in next[..n].iter_mut().enumerate() {
+                            let s = S::sum((0..n).map(|i| S::mul(prev[i], self.ln_trans[i * n + j])));
+                            *a = S::mul(ln_emit[t * n + j], s);
+                        }
+                    }
+                    alpha
+                }
+
+                /// Return the backward recursion in the semiring `S`,
+                /// `b[T-1][i] = one` and
+                /// `b[t][i] = sum_j(trans[i][j] * emit[t+1][j] * b[t+1][j])`.
+                /// In the [`LogSemiring`], these are the log backward variables,
+                /// `ln P(o_{t+1}, ..., o_{T-1} | s_t = i)`.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn backward_in<S: Semiring<$f>>(&self, ln_emit: &[$f]) -> Vec<$f> {
+                    let n = self.n_states();
+                    let steps = self.steps(ln_emit);
+                    let mut beta = vec![S::one(); steps * n];
+                    for t in (0..steps.saturating_sub(1)).rev() {
+                        let (this, next) = beta.split_at_mut((t + 1) * n);
+                        let emit = &ln_emit[(t + 1) * n..(t + 2) * n];
+                        for (i, b) in this[t * n..].iter_mut().enumerate() {
+                            let row = &self.ln_trans[i * n..(i + 1) * n];
+                            *b = S::sum((0..n).map(|j| S::mul(row[j], S::mul(emit[j], next[j]))));
+                        }
+                    }
+                    beta
+                }
+
+                /// Return the log forward variables, `ln P(o_0, ..., o_t, s_t = j)`.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn forward(&self, ln_emit: &[$f]) -> Vec<$f> {
+                    self.forward_in::<LogSemiring>(ln_emit)
+                }
+
+                /// Return the log backward variables,
+                /// `ln P(o_{t+1}, ..., o_{T-1} | s_t = i)`.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn backward(&self, ln_emit: &[$f]) -> Vec<$f> {
+                    self.backward_in::<LogSemiring>(ln_emit)
+                }
+
+                /// Return the log-likelihood of the observations,
+                /// `ln P(o_0, ..., o_{T-1})`.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn ln_likelihood(&self, ln_emit: &[$f]) -> $f {
+                    let n = self.n_states();
+                    let alpha = self.forward(ln_emit);
+                    if alpha.is_empty() {
+                        0.0
+                    } else {
+                        alpha[alpha.len() - n..].iter().ln_sum_exp()
+                    }
+                }
+
+                /// Return the log-likelihood, and the log posterior state marginals and
+                /// transition probabilities, by the forward-backward algorithm.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn posteriors(&self, ln_emit: &[$f]) -> Posteriors<$f> {
+                    let n = self.n_states();
+                    let steps = self.steps(ln_emit);
+                    let alpha = self.forward(ln_emit);
+                    let beta = self.backward(ln_emit);
+                    let ln_likelihood = if steps == 0 {
+                        0.0
+                    } else {
+                        alpha[(steps - 1) * n..].iter().ln_sum_exp()
+                    };
+
+                    let ln_state = alpha
+                        .iter()
+                        .zip(beta.iter())
+                        .map(|(a, b)| a + b - ln_likelihood)
+                        .collect();
+
+                    let mut ln_transition = Vec::with_capacity(steps.saturating_sub(1) * n * n);
+                    for t in 0..steps.saturating_sub(1) {
+                        for i in 0..n {
+                            let a = alpha[t * n + i] - ln_likelihood;
+                            for j in 0..n {
+                                let k = (t + 1) * n + j;
+                                ln_transition.push(a + self.ln_trans[i * n + j] + ln_emit[k] + beta[k]);
+                            }
+                        }
+                    }
+
+                    Posteriors {
+                        ln_likelihood,
+                        ln_state,
+                        ln_transition,
+                    }
+                }
+
+                /// Return the log probability of the most probable sequence of states,
+                /// jointly with the observations, and the sequence, by the Viterbi
+                /// algorithm, i.e. the forward recursion in [`MaxPlus`] followed by
+                /// backtracking. Ties are broken in favour of the lowest state.
+                ///
+                /// # Panics
+                /// If the length of `ln_emit` is not a multiple of the number of states.
+                pub fn viterbi(&self, ln_emit: &[$f]) -> ($f, Vec<usize>) {
+                    let n = self.n_states();
+                    let steps = self.steps(ln_emit);
+                    if steps == 0 {
+                        return (0.0, Vec::new());
+                    }
+                    let delta = self.forward_in::<MaxPlus>(ln_emit);
+
+                    // The index and value of the first maximum; as for `MaxPlus`, `nan` wins.
+                    fn argmax(scores: impl Iterator<Item = $f>) -> (usize, $f) {
+                        let mut best = (0, $f::NEG_INFINITY);
+                        for (i, s) in scores.enumerate() {
+                            if i == 0 || s > best.1 || (s.is_nan() && !best.1.is_nan()) {
+                                best = (i, s);
+                            }
+                        }
+                        best
+                    }
+
+                    let mut path = vec![0; steps];
+                    let (last, score) = argmax(delta[(steps - 1) * n..].iter().copied());
+                    path[steps - 1] = last;
+                    for t in (0..steps - 1).rev() {
+                        let j = path[t + 1];
+                        let scores = (0..n).map(|i| delta[t * n + i] + self.ln_trans[i * n + j]);
+                        path[t] = argmax(scores).0;
+                    }
+                    (score, path)
+                }
+            }
+        )+
+    }
+}
+impl_hmm! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Real;
+
+    macro_rules! hmm_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                const N: usize = 3;
+                const STEPS: usize = 4;
+
+                fn ln(v: &[$f]) -> Vec<$f> {
+                    v.iter().map(|p| p.ln()).collect()
+                }
+
+                // A model and observations on the linear scale.
+                fn model() -> (Vec<$f>, Vec<$f>, Vec<$f>) {
+                    let init = vec![0.5, 0.3, 0.2];
+                    let trans = vec![0.7, 0.2, 0.1, 0.1, 0.6, 0.3, 0.25, 0.25, 0.5];
+                    let emit = vec![0.9, 0.2, 0.1, 0.1, 0.7, 0.3, 0.5, 0.5, 0.5, 0.1, 0.1, 0.8];
+                    (init, trans, emit)
+                }
+
+                // Every sequence of states, with its joint probability with the observations.
+                fn paths(init: &[$f], trans: &[$f], emit: &[$f]) -> Vec<(Vec<usize>, $f)> {
+                    let mut out = Vec::new();
+                    for code in 0..N.pow(STEPS as u32) {
+                        let path: Vec<usize> = (0..STEPS).map(|t| code / N.pow(t as u32) % N).collect();
+                        let mut p = init[path[0]] * emit[path[0]];
+                        for t in 1..STEPS {
+                            p *= trans[path[t - 1] * N + path[t]] * emit[t * N + path[t]];
+                        }
+                        out.push((path, p));
+                    }
+                    out
+                }
+
+                fn close(a: $f, b: $f) -> bool {
+                    (a - b).abs() <= 64.0 * $f::EPSILON * b.abs().max(1.0)
+                }
+
+                #[test]
+                fn posteriors_works() {
+                    let (init, trans, emit) = model();
+                    let (ln_init, ln_trans, ln_emit) = (ln(&init), ln(&trans), ln(&emit));
+                    let hmm = Hmm::new(&ln_init, &ln_trans);
+                    assert_eq!(hmm.n_states(), N);
+
+                    let paths = paths(&init, &trans, &emit);
+                    let likelihood: $f = paths.iter().map(|(_, p)| p).sum();
+                    assert!(close(hmm.ln_likelihood(&ln_emit), likelihood.ln()));
+
+                    let posteriors = hmm.posteriors(&ln_emit);
+                    assert!(close(posteriors.ln_likelihood, likelihood.ln()));
+                    assert_eq!(posteriors.ln_state.len(), STEPS * N);
+                    assert_eq!(posteriors.ln_transition.len(), (STEPS - 1) * N * N);
+                    for t in 0..STEPS {
+                        for i in 0..N {
+                            let p: $f = paths.iter().filter(|(s, _)| s[t] == i).map(|(_, p)| p).sum();
+                            assert!(close(posteriors.ln_state[t * N + i].exp(), p / likelihood));
+                        }
+                    }
+                    for t in 0..STEPS - 1 {
+                        for i in 0..N {
+                            for j in 0..N {
+                                let p: $f = paths
+                                    .iter()
+                                    .filter(|(s, _)| s[t] == i && s[t + 1] == j)
+                                    .map(|(_, p)| p)
+                                    .sum();
+                                let lhs = posteriors.ln_transition[(t * N + i) * N + j].exp();
+                                assert!(close(lhs, p / likelihood));
+                            }
+                        }
+                    }
+                }
+
+                #[test]
+                fn forward_backward_agree() {
+                    let (init, trans, emit) = model();
+                    let (ln_init, ln_trans, ln_emit) = (ln(&init), ln(&trans), ln(&emit));
+                    let hmm = Hmm::new(&ln_init, &ln_trans);
+
+                    let alpha = hmm.forward(&ln_emit);
+                    let beta = hmm.backward(&ln_emit);
+                    let ll = hmm.ln_likelihood(&ln_emit);
+                    for t in 0..STEPS {
+                        let lhs = (0..N).map(|i| alpha[t * N + i] + beta[t * N + i]).ln_sum_exp();
+                        assert!(close(lhs, ll));
+                    }
+                    assert_eq!(&beta[(STEPS - 1) * N..], &[0.0; N]);
+
+                    // the same recursions on the linear scale
+                    let hmm = Hmm::new(&init, &trans);
+                    let real = hmm.forward_in::<Real>(&emit);
+                    for (a, b) in alpha.iter().zip(real.iter()) {
+                        assert!(close(a.exp(), *b));
+                    }
+                    let real = hmm.backward_in::<Real>(&emit);
+                    for (a, b) in beta.iter().zip(real.iter()) {
+                        assert!(close(a.exp(), *b));
+                    }
+                }
+
+                #[test]
+                fn viterbi_works() {
+                    let (init, trans, emit) = model();
+                    let (ln_init, ln_trans, ln_emit) = (ln(&init), ln(&trans), ln(&emit));
+                    let hmm = Hmm::new(&ln_init, &ln_trans);
+
+                    let paths = paths(&init, &trans, &emit);
+                    let best = paths.iter().fold(&paths[0], |b, x| if x.1 > b.1 { x } else { b });
+                    let (score, path) = hmm.viterbi(&ln_emit);
+                    assert_eq!(path, best.0);
+                    assert!(close(score, best.1.ln()));
+
+                    // an impossible transition
+                    let mut ln_trans = ln_trans.clone();
+                    ln_trans[best.0[0] * N + best.0[1]] = $f::NEG_INFINITY;
+                    let hmm = Hmm::new(&ln_init, &ln_trans);
+                    let (score, path) = hmm.viterbi(&ln_emit);
+                    assert_ne!(path, best.0);
+                    assert!(score < best.1.ln());
+                }
+
+                #[test]
+                fn special_cases() {
+                    let (init, trans, emit) = model();
+                    let (ln_init, ln_trans, ln_emit) = (ln(&init), ln(&trans), ln(&emit));
+                    let hmm = Hmm::new(&ln_init, &ln_trans);
+
+                    // no observations
+                    assert_eq!(hmm.ln_likelihood(&[]), 0.0);
+                    assert_eq!(hmm.viterbi(&[]), (0.0, vec![]));
+                    let posteriors = hmm.posteriors(&[]);
+                    assert!(posteriors.ln_state.is_empty() && posteriors.ln_transition.is_empty());
+
+                    // a single observation
+                    let posteriors = hmm.posteriors(&ln_emit[..N]);
+                    let rhs = (0..N).map(|i| ln_init[i] + ln_emit[i]).ln_sum_exp();
+                    assert_eq!(posteriors.ln_likelihood, rhs);
+                    assert!(posteriors.ln_transition.is_empty());
+
+                    // impossible observations
+                    let mut ln_emit = ln_emit.clone();
+                    ln_emit[N..2 * N].fill($f::NEG_INFINITY);
+                    assert_eq!(hmm.ln_likelihood(&ln_emit), $f::NEG_INFINITY);
+                    assert!(hmm.posteriors(&ln_emit).ln_state.iter().all(|x| x.is_nan()));
+                    assert_eq!(hmm.viterbi(&ln_emit).0, $f::NEG_INFINITY);
+                }
+
+                #[test]
+                #[should_panic(expected = "at least one state")]
+                fn states_are_checked() {
+                    Hmm::<$f>::new(&[], &[]);
+                }
+
+                #[test]
+                #[should_panic(expected = "n columns")]
+                fn emission_shape_is_checked() {
+                    let (init, trans, emit) = model();
+                    let (ln_init, ln_trans, ln_emit) = (ln(&init), ln(&trans), ln(&emit));
+                    Hmm::new(&ln_init, &ln_trans).forward(&ln_emit[1..]);
+                }
+            }
+        }
+    }
+    hmm_tests! { f64_hmm_impl f64 }
+    hmm_tests! { f32_hmm_impl f32 }
+}
@@ -3,13 +3,115 @@
 //! Numerically stable evaluation of `ln(exp(a) + exp(b))` via the `LogAddExp` trait,
 //! and a numerically stable, 1-pass (online) algorithm for evaluation of [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
 //! via the `LogSumExp` trait.
+//!
+//! # Contents
+//!
+//! * `LogSubExp`: `ln(exp(a) - exp(b))`.
+//! * `OnlineLogSumExp`: the state of the online algorithm, with push and merge.
+//! * `WeightedLogSumExp` and `SignedLogSumExp`: weighted sums, and signed coefficients.
+//! * `CheckedLogSumExp`: reports problems with the data as errors.
+//! * `LogCumSumExp` and `SliceLogCumSumExp`: running (prefix or suffix) LogSumExp.
+//! * `Softmax`: softmax and log softmax of a slice.
+//! * `LogSumExpGrad`: derivatives of LogSumExp and the softmax.
+//! * `OnlineAttention` and `SoftmaxWeightedSum`: one-pass softmax-weighted sums of values.
+//! * `CrossEntropy` and `ChunkedCrossEntropy`: fused cross-entropy of logits and its gradient.
+//! * `SmoothMax` and `SmoothMax2`: temperature-scaled smooth maxima and minima.
+//! * `LogF64` and `LogF32`: values represented by their logarithms.
+//! * `Semiring`: the log, max-plus, min-plus and real semirings.
+//! * `Hmm`: forward-backward and Viterbi algorithms over a semiring.
+//! * `LogMatMul`: matrix products in the log semiring.
+//! * `SliceLogSumExp`: vectorized LogSumExp over slices.
+//! * `CompensatedLogSumExp` and `PairwiseLogSumExp`: compensated and pairwise summation.
+//! * `MixedLogSumExp`: LogSumExp over `f32` items, evaluated in `f64`.
+//! * `PreciseLogSumExp` and `PreciseLogAddExp`: nearly correctly-rounded, in double-double.
+//!
+//! # Features
+//!
+//! * `std` (default): the floating point functions of `std`.
+//! * `libm`: the floating point functions of `libm`, for `no_std` (without default features).
+//! * `half`: `LogAddExp`, `LogSubExp` and `LogSumExp` for `half::f16` and `half::bf16`.
+//! * `num-traits`: `LogAddExp`, `LogSubExp` and `LogSumExp` for `T: num_traits::Float`.
+//! * `ndarray`: `ArrayLogSumExp`, LogSumExp and softmax along an axis of an array.
+//! * `rayon`: `ParallelLogSumExp` and `ParallelSliceLogSumExp`, parallel evaluation.
+//! * `rand`: `GumbelSample`, sampling from unnormalized log weights by the Gumbel-max trick.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("either the `std` feature or the `libm` feature must be enabled");
+
+extern crate alloc;
 
+#[cfg(all(feature = "std", not(feature = "num-traits")))]
 use lnexp::LnExp;
+#[cfg(not(any(feature = "std", feature = "num-traits")))]
+use math::LnExp;
+use math::{exp, ln};
+
+mod accumulator;
+#[cfg(feature = "ndarray")]
+mod array;
+mod attention;
+mod checked;
+mod compensated;
+mod cross_entropy;
+mod cumulative;
+#[cfg(feature = "half")]
+mod float16;
+#[cfg(feature = "num-traits")]
+mod generic;
+mod grad;
+mod hmm;
+mod logspace;
+mod math;
+mod matmul;
+mod mixed;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod precise;
+#[cfg(feature = "rand")]
+mod sample;
+mod semiring;
+mod signed;
+mod simd;
+mod smooth;
+mod softmax;
+mod weighted;
+
+pub use accumulator::OnlineLogSumExp;
+#[cfg(feature = "ndarray")]
+pub use array::ArrayLogSumExp;
+pub use attention::{OnlineAttention, SoftmaxWeightedSum};
+pub use checked::{CheckedLogSumExp, LogSumExpError, NanPolicy};
+pub use compensated::{CompensatedLogSumExp, PairwiseLogSumExp};
+pub use cross_entropy::{ChunkedCrossEntropy, CrossEntropy};
+pub use cumulative::{LnCumSumExp, LogCumSumExp, SliceLogCumSumExp};
+pub use grad::LogSumExpGrad;
+pub use hmm::{Hmm, Posteriors};
+pub use logspace::{LogF32, LogF64};
+pub use matmul::LogMatMul;
+pub use mixed::MixedLogSumExp;
+#[cfg(feature = "rayon")]
+pub use parallel::{ParallelLogSumExp, ParallelSliceLogSumExp};
+pub use precise::{PreciseLogAddExp, PreciseLogSumExp};
+#[cfg(feature = "rand")]
+pub use sample::GumbelSample;
+pub use semiring::{LogSemiring, MaxPlus, MinPlus, Real, Semiring, SemiringReduce};
+pub use signed::{CancellationError, SignedLogSumExp};
+pub use simd::{SliceLogSumExp, SLICE_ULP_BOUND};
+pub use smooth::{SmoothMax, SmoothMax2};
+pub use softmax::Softmax;
+pub use weighted::WeightedLogSumExp;
 
 /// A trait which, for the type on which it is implemented,
 /// provides numerically-stable evaluation of `ln(exp(a) + exp(b))`.
 /// The implementations provided for `f64` (and `&f64`) and `f32` (and `&f32`) utilize [`ln_1p_exp`](https://docs.rs/lnexp/0.2.0/lnexp/trait.LnExp.html#tymethod.ln_1p_exp)
 /// for maximum stability.
+///
+/// The mixed implementations, of `f32` with `f64` (and `&f64`) and of `f64` with `f32`
+/// (and `&f32`), evaluate in, and return, `f64`. As a consequence, the type of an
+/// unsuffixed literal argument, e.g. `x.ln_add_exp(1.0)` for `x: f32`, is ambiguous, and
+/// defaults to `f64`; write `1.0_f32` for the `f32` result.
 pub trait LogAddExp<Rhs = Self> {
     type Output;
 
@@ -36,6 +138,7 @@ pub trait LogAddExp<Rhs = Self> {
     fn ln_add_exp(&self, rhs: Rhs) -> Self::Output;
 }
 
+#[cfg(not(feature = "num-traits"))]
 macro_rules! impl_logaddexp {
     { $($f:ident)+ } => {
         $(
@@ -64,8 +167,79 @@ macro_rules! impl_logaddexp {
 
     };
 }
+#[cfg(not(feature = "num-traits"))]
 impl_logaddexp! { f64 f32 }
 
+/// A trait which, for the type on which it is implemented,
+/// provides numerically-stable evaluation of `ln(exp(a) - exp(b))`.
+/// The implementations provided for `f64` (and `&f64`) and `f32` (and `&f32`) utilize [`ln_1m_exp`](https://docs.rs/lnexp/0.2.0/lnexp/trait.LnExp.html#tymethod.ln_1m_exp)
+/// for maximum stability.
+pub trait LogSubExp<Rhs = Self> {
+    type Output;
+
+    /// Return the log of the difference of exponentials of `self` and `rhs`.
+    /// If `rhs > self`, the result is undefined, hence, the return value is `nan`;
+    /// if `rhs == self`, the return value is `-inf`, unless both are `inf`, in which
+    /// case the difference is undefined.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::LogSubExp;
+    ///
+    /// let x: f64 = 1.0;
+    /// let y: f64 = 0.5;
+    /// let z: f64 = x + (-(y - x).exp_m1()).ln();
+    /// assert_eq!(x.ln_sub_exp(y), z);
+    /// assert_eq!(x.ln_sub_exp(&y), z);
+    /// assert_eq!((&x).ln_sub_exp(y), z);
+    /// assert_eq!((&x).ln_sub_exp(&y), z);
+    ///
+    /// assert_eq!(x.ln_sub_exp(x), f64::NEG_INFINITY);
+    /// assert!(y.ln_sub_exp(x).is_nan());
+    ///
+    /// // near-equal arguments
+    /// let d: f64 = (2.0_f64).powi(-40);
+    /// let x: f64 = 1.0;
+    /// let y: f64 = x - d;
+    /// assert!((x.ln_sub_exp(y) - (x + d.ln())).abs() < 1e-9);
+    /// // compare to naive computation
+    /// assert!(((x.exp() - y.exp()).ln() - (x + d.ln())).abs() > 1e-6);
+    /// ```
+    fn ln_sub_exp(&self, rhs: Rhs) -> Self::Output;
+}
+
+#[cfg(not(feature = "num-traits"))]
+macro_rules! impl_logsubexp {
+    { $($f:ident)+ } => {
+        $(
+            impl LogSubExp for $f {
+                type Output = $f;
+                fn ln_sub_exp(&self, rhs: Self) -> Self::Output {
+                    if *self == rhs {
+                        if rhs == $f::INFINITY {
+                            $f::NAN
+                        } else {
+                            $f::NEG_INFINITY
+                        }
+                    } else {
+                        // rhs > self yields nan from `ln_1m_exp`
+                        *self + (rhs - *self).ln_1m_exp()
+                    }
+                }
+            }
+            impl LogSubExp<&$f> for $f {
+                type Output = $f;
+                fn ln_sub_exp(&self, rhs: &$f) -> Self::Output {
+                    self.ln_sub_exp(*rhs)
+                }
+            }
+        )+
+
+    };
+}
+#[cfg(not(feature = "num-traits"))]
+impl_logsubexp! { f64 f32 }
+
 /// A trait for computing the log of the sum of exponentials of a sequence
 /// in a numerically-stable manner, using a 1-pass (online) algorithm based on
 /// [Milakov, Maxim, and Natalia Gimelshein. "Online normalizer calculation for softmax." (2018)](https://arxiv.org/pdf/1805.02867.pdf).
@@ -158,14 +332,15 @@ macro_rules! impl_logsumexp {
                         } else {
                             // finite and not nan
                             let m_new = m_old.max(v_i);
-                            sum = sum * (m_old - m_new).exp() + (v_i - m_new).exp();
+                            sum = sum * exp(m_old - m_new) + exp(v_i - m_new);
                             m_old = m_new;
                         }
                     }
-                    m_old + sum.ln()
+                    m_old + ln(sum)
                 }
             }
 
+            #[cfg(not(feature = "num-traits"))]
             impl<'a, U> LogSumExp<&'a $f, U> for U
             where
                 U: Iterator<Item = &'a $f>,
@@ -188,11 +363,11 @@ macro_rules! impl_logsumexp {
                             return *v_i
                         } else {
                             let m_new = m_old.max(*v_i);
-                            sum = sum * (m_old - m_new).exp() + (*v_i - m_new).exp();
+                            sum = sum * exp(m_old - m_new) + exp(*v_i - m_new);
                             m_old = m_new;
                         }
                     }
-                    m_old + sum.ln()
+                    m_old + ln(sum)
                 }
             }
         )+
@@ -201,9 +376,52 @@ macro_rules! impl_logsumexp {
 }
 impl_logsumexp! { f64 f32 }
 
+#[cfg(test)]
+pub(crate) mod test_util {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    pub(crate) trait Uniform: Sized {
+        // The value in [0, 1) given by the leading bits of `state`.
+        fn from_bits(state: u64) -> Self;
+        fn affine(lo: Self, hi: Self, u: Self) -> Self;
+    }
+
+    macro_rules! impl_uniform {
+        { $($f:ident)+ } => {
+            $(
+                impl Uniform for $f {
+                    fn from_bits(state: u64) -> Self {
+                        ((state >> (64 - $f::MANTISSA_DIGITS)) as $f) / ((1_u64 << $f::MANTISSA_DIGITS) as $f)
+                    }
+                    fn affine(lo: Self, hi: Self, u: Self) -> Self {
+                        lo + (hi - lo) * u
+                    }
+                }
+            )+
+        }
+    }
+    impl_uniform! { f64 f32 }
+
+    // A deterministic sequence of values in [lo, hi).
+    pub(crate) fn values<T: Uniform + Copy>(n: usize, lo: T, hi: T) -> Vec<T> {
+        let mut state: u64 = 0x853c49e6748fea9b;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                T::affine(lo, hi, T::from_bits(state))
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
 
     macro_rules! ln_add_exp_tests {
         { $name:ident $f:ident } => {
@@ -279,6 +497,107 @@ mod tests {
     ln_add_exp_tests! { f64_logaddexp_impl f64 }
     ln_add_exp_tests! { f32_logaddexp_impl f32 }
 
+    macro_rules! ln_sub_exp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+                #[test]
+                fn ln_sub_exp_works() {
+                    let inf = $f::INFINITY;
+                    let nan: $f = $f::NAN;
+
+                    // Cases involving +/-inf
+                    let u: $f = inf;
+                    let v: $f = inf;
+                    assert!(u.ln_sub_exp(v).is_nan());
+
+                    let u: $f = -inf;
+                    let v: $f = -inf;
+                    assert_eq!(u.ln_sub_exp(v), -inf);
+
+                    let u: $f = inf;
+                    let v: $f = -inf;
+                    assert_eq!(u.ln_sub_exp(v), inf);
+
+                    let u: $f = -inf;
+                    let v: $f = inf;
+                    assert!(u.ln_sub_exp(v).is_nan());
+
+                    let u: $f = inf;
+                    let v: $f = 0.5;
+                    assert_eq!(u.ln_sub_exp(v), inf);
+
+                    let u: $f = -inf;
+                    let v: $f = 0.5;
+                    assert!(u.ln_sub_exp(v).is_nan());
+
+                    let u: $f = 0.5;
+                    let v: $f = inf;
+                    assert!(u.ln_sub_exp(v).is_nan());
+
+                    let u: $f = 0.5;
+                    let v: $f = -inf;
+                    assert_eq!(u.ln_sub_exp(v), u);
+
+                    // Cases involving nan
+                    assert!(nan.ln_sub_exp(inf).is_nan());
+                    assert!(nan.ln_sub_exp(-inf).is_nan());
+                    assert!(inf.ln_sub_exp(nan).is_nan());
+                    assert!((-inf).ln_sub_exp(nan).is_nan());
+                    assert!(nan.ln_sub_exp(nan).is_nan());
+
+                    let x: $f = 0.5;
+                    assert!(nan.ln_sub_exp(x).is_nan());
+                    assert!(nan.ln_sub_exp(-x).is_nan());
+                    assert!(x.ln_sub_exp(nan).is_nan());
+                    assert!((-x).ln_sub_exp(nan).is_nan());
+
+                    // Finite cases
+                    let u: $f = 0.5;
+                    assert_eq!(u.ln_sub_exp(u), -inf);
+                    assert!((-u).ln_sub_exp(u).is_nan());
+
+                    let u: $f = 2.0;
+                    let v: $f = 1.0;
+                    let w: $f = (u.exp() - v.exp()).ln();
+                    assert!((u.ln_sub_exp(v) - w).abs() < 2.0 * $f::EPSILON);
+
+                    // inverse of ln_add_exp
+                    let u: $f = 1.5;
+                    let v: $f = -0.5;
+                    assert!((u.ln_add_exp(v).ln_sub_exp(v) - u).abs() < 2.0 * $f::EPSILON);
+                }
+
+                #[test]
+                fn ln_sub_exp_works_argtypes() {
+                    let x: $f = 1.0;
+                    let y: $f = 0.5;
+                    let z: $f = x + (-(y - x).exp_m1()).ln();
+                    assert_eq!(x.ln_sub_exp(y), z);
+                    assert_eq!(x.ln_sub_exp(&y), z);
+                    let x_ref = &x;
+                    assert_eq!(x_ref.ln_sub_exp(y), z);
+                    assert_eq!(x_ref.ln_sub_exp(&y), z);
+                }
+
+                #[test]
+                fn ln_sub_exp_near_equal() {
+                    // naive evaluation loses all precision as rhs approaches self
+                    let x: $f = 1.0;
+                    let y: $f = 1.0 - 8.0 * $f::EPSILON;
+                    let lhs = x.ln_sub_exp(y);
+                    assert!(lhs.is_finite());
+                    let rhs = x + (-(y - x).exp_m1()).ln();
+                    assert_eq!(lhs, rhs);
+                }
+            }
+        }
+    }
+
+    ln_sub_exp_tests! { f64_logsubexp_impl f64 }
+    ln_sub_exp_tests! { f32_logsubexp_impl f32 }
+
     macro_rules! ln_sum_exp_tests {
         { $name:ident $f:ident } => {
             #[cfg(test)]
@@ -484,6 +803,9 @@ mod tests {
                     let rhs: $f = $f::ln(6.0);
                     assert!((into_iter.ln_sum_exp() -  rhs).abs() < 2.0 * $f::EPSILON);
 
+                    #[cfg(not(feature = "std"))]
+                    use alloc::collections::BTreeMap as HashMap;
+                    #[cfg(feature = "std")]
                     use std::collections::HashMap;
                     let map: HashMap<i32, $f> = HashMap::from([(1, 0.5), (2, 1.0), (3, 1.5)]);
                     let iter = map.values().map(|x| x.ln());
@@ -493,7 +815,7 @@ mod tests {
 
                 #[test]
                 fn ln_sum_exp_associativity_works() {
-                    use std::iter;
+                    use core::iter;
                     let v: Vec<$f> = vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0];
                     let (whole, frac): (Vec<$f>, Vec<$f>) = v.iter().partition(|x| (*x).floor() == **x);
                     let iter = iter::once(whole.iter().ln_sum_exp()).chain(iter::once(frac.iter().ln_sum_exp()));
@@ -0,0 +1,335 @@
+use crate::math::{exp, ln};
+use crate::{LogAddExp, LogSubExp, LogSumExp};
+use core::iter::{Product, Sum};
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+
+/// A non-negative real number represented by its natural logarithm, held in an `f64`,
+/// such that the arithmetic operators implement the log semiring: `a + b` evaluates
+/// `ln(exp(a) + exp(b))` via [`LogAddExp`], `a * b` and `a / b` add and subtract the
+/// logarithms, [`Sum`] uses [`LogSumExp`] and [`Product`] sums the logarithms. `a - b`
+/// evaluates `ln(exp(a) - exp(b))` via [`LogSubExp`], hence, is `nan` if `b > a`.
+///
+/// Zero is represented by `-inf`, which is the value of [`Default`], and one by `0`.
+/// The comparison operators compare the logarithms, hence, the linear-scale values.
+/// With the `num-traits` feature enabled, `Zero`, `One`, `Inv` and `Pow` are
+/// implemented.
+///
+/// # Examples
+/// ```
+/// use logsumexp::LogF64;
+///
+/// let p = LogF64::from_linear(0.25);
+/// let q = LogF64::from_ln(0.5_f64.ln());
+/// assert!(((p + q).to_linear() - 0.75).abs() < f64::EPSILON);
+/// assert!(((p * q).to_linear() - 0.125).abs() < f64::EPSILON);
+/// assert!(((p / q).to_linear() - 0.5).abs() < f64::EPSILON);
+/// assert!(((q - p).to_linear() - 0.25).abs() < f64::EPSILON);
+///
+/// let v = [p, q, p];
+/// assert!((v.iter().sum::<LogF64>().ln()).abs() < f64::EPSILON);
+/// assert!((v.iter().product::<LogF64>().to_linear() - 0.03125).abs() < f64::EPSILON);
+///
+/// // probabilities far below the smallest positive f64
+/// let tiny = LogF64::from_ln(-1000.0);
+/// assert_eq!((tiny * tiny).ln(), -2000.0);
+/// assert_eq!((tiny + tiny).ln(), -1000.0 + 2.0_f64.ln());
+/// assert_eq!(LogF64::default(), LogF64::ZERO);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct LogF64(f64);
+
+/// A non-negative real number represented by its natural logarithm, held in an `f32`.
+/// See [`LogF64`].
+///
+/// # Examples
+/// ```
+/// use logsumexp::LogF32;
+///
+/// let p = LogF32::from_linear(0.25);
+/// let q = LogF32::from_linear(0.5);
+/// assert!(((p + q).to_linear() - 0.75).abs() < f32::EPSILON);
+/// assert_eq!(p * LogF32::ONE, p);
+/// assert_eq!(p + LogF32::ZERO, p);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct LogF32(f32);
+
+macro_rules! impl_log_float {
+    { $($t:ident $f:ident)+ } => {
+        $(
+            impl $t {
+                /// The additive identity, i.e. the representation of zero, `-inf`.
+                pub const ZERO: Self = Self($f::NEG_INFINITY);
+                /// The multiplicative identity, i.e. the representation of one, `0`.
+                pub const ONE: Self = Self(0.0);
+
+                /// Create a value from its natural logarithm.
+                pub const fn from_ln(ln: $f) -> Self {
+                    Self(ln)
+                }
+
+                /// Create a value from its linear-scale value, which must be non-negative;
+                /// a negative value results in `nan`.
+                pub fn from_linear(x: $f) -> Self {
+                    Self(ln(x))
+                }
+
+                /// Return the natural logarithm of the value.
+                pub const fn ln(self) -> $f {
+                    self.0
+                }
+
+                /// Return the linear-scale value, which underflows to zero, or overflows to
+                /// `inf`, if the logarithm is out of the range of `exp`.
+                pub fn to_linear(self) -> $f {
+                    exp(self.0)
+                }
+
+                /// Return true if the value is zero, i.e. the logarithm is `-inf`.
+                pub fn is_zero(&self) -> bool {
+                    self.0 == $f::NEG_INFINITY
+                }
+            }
+
+            impl Default for $t {
+                fn default() -> Self {
+                    Self::ZERO
+                }
+            }
+
+            impl Add for $t {
+                type Output = $t;
+                fn add(self, rhs: Self) -> Self::Output {
+                    Self(self.0.ln_add_exp(rhs.0))
+                }
+            }
+
+            impl AddAssign for $t {
+                fn add_assign(&mut self, rhs: Self) {
+                    *self = *self + rhs;
+                }
+            }
+
+            impl Sub for $t {
+                type Output = $t;
+                fn sub(self, rhs: Self) -> Self::Output {
+                    Self(self.0.ln_sub_exp(rhs.0))
+                }
+            }
+
+            impl SubAssign for $t {
+                fn sub_assign(&mut self, rhs: Self) {
+                    *self = *self - rhs;
+                }
+            }
+
+            impl Mul for $t {
+                type Output = $t;
+                // Multiplication in the log semiring adds the logarithms.
+                #[allow(clippy::suspicious_arithmetic_impl)]
+                fn mul(self, rhs: Self) -> Self::Output {
+                    Self(self.0 + rhs.0)
+                }
+            }
+
+            impl MulAssign for $t {
+                fn mul_assign(&mut self, rhs: Self) {
+                    *self = *self * rhs;
+                }
+            }
+
+            impl Div for $t {
+                type Output = $t;
+                #[allow(clippy::suspicious_arithmetic_impl)]
+                fn div(self, rhs: Self) -> Self::Output {
+                    Self(self.0 - rhs.0)
+                }
+            }
+
+            impl DivAssign for $t {
+                fn div_assign(&mut self, rhs: Self) {
+                    *self = *self / rhs;
+                }
+            }
+
+            impl Sum for $t {
+                fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                    Self(iter.map(|x| x.0).ln_sum_exp())
+                }
+            }
+
+            impl<'a> Sum<&'a $t> for $t {
+                fn sum<I: Iterator<Item = &'a $t>>(iter: I) -> Self {
+                    Self(iter.map(|x| x.0).ln_sum_exp())
+                }
+            }
+
+            impl Product for $t {
+                fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+                    Self(iter.fold(0.0, |a, x| a + x.0))
+                }
+            }
+
+            impl<'a> Product<&'a $t> for $t {
+                fn product<I: Iterator<Item = &'a $t>>(iter: I) -> Self {
+                    Self(iter.fold(0.0, |a, x| a + x.0))
+                }
+            }
+
+            #[cfg(feature = "num-traits")]
+            impl num_traits::Zero for $t {
+                fn zero() -> Self {
+                    Self::ZERO
+                }
+                fn is_zero(&self) -> bool {
+                    $t::is_zero(self)
+                }
+            }
+
+            #[cfg(feature = "num-traits")]
+            impl num_traits::One for $t {
+                fn one() -> Self {
+                    Self::ONE
+                }
+                fn is_one(&self) -> bool {
+                    self.0 == 0.0
+                }
+            }
+
+            #[cfg(feature = "num-traits")]
+            impl num_traits::Inv for $t {
+                type Output = $t;
+                fn inv(self) -> Self::Output {
+                    Self(-self.0)
+                }
+            }
+
+            #[cfg(feature = "num-traits")]
+            impl num_traits::Pow<$f> for $t {
+                type Output = $t;
+                fn pow(self, rhs: $f) -> Self::Output {
+                    // 0^0 is 1, whereas -inf * 0 is nan.
+                    if rhs == 0.0 {
+                        Self::ONE
+                    } else {
+                        Self(self.0 * rhs)
+                    }
+                }
+            }
+        )+
+    }
+}
+impl_log_float! { LogF64 f64 LogF32 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! log_float_tests {
+        { $name:ident $t:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn arithmetic_works() {
+                    let x: $f = 0.5;
+                    let y: $f = 1.25;
+                    let a = $t::from_ln(x);
+                    let b = $t::from_ln(y);
+
+                    assert_eq!((a + b).ln(), x.ln_add_exp(y));
+                    assert_eq!((b - a).ln(), y.ln_sub_exp(x));
+                    assert_eq!((a * b).ln(), x + y);
+                    assert_eq!((a / b).ln(), x - y);
+
+                    let mut c = a;
+                    c += b;
+                    assert_eq!(c, a + b);
+                    c -= b;
+                    assert_eq!(c, a + b - b);
+                    c *= b;
+                    assert_eq!(c, (a + b - b) * b);
+                    c /= b;
+                    assert_eq!(c, (a + b - b) * b / b);
+
+                    assert!((a - b).ln().is_nan());
+                    assert!(a < b);
+                }
+
+                #[test]
+                fn identities() {
+                    let a = $t::from_ln(0.5);
+                    assert_eq!(a + $t::ZERO, a);
+                    assert_eq!($t::ZERO + a, a);
+                    assert_eq!(a * $t::ONE, a);
+                    assert_eq!(a * $t::ZERO, $t::ZERO);
+                    assert_eq!(a - a, $t::ZERO);
+                    assert_eq!(a / a, $t::ONE);
+                    assert_eq!($t::ZERO + $t::ZERO, $t::ZERO);
+                    assert!($t::ZERO.is_zero());
+                    assert!(!$t::ONE.is_zero());
+                    assert_eq!($t::default(), $t::ZERO);
+                }
+
+                #[test]
+                fn conversions() {
+                    assert_eq!($t::from_linear(1.0), $t::ONE);
+                    assert_eq!($t::from_linear(0.0), $t::ZERO);
+                    assert!($t::from_linear(-1.0).ln().is_nan());
+                    assert_eq!($t::ONE.to_linear(), 1.0);
+                    assert_eq!($t::ZERO.to_linear(), 0.0);
+                    let x: $f = 0.375;
+                    assert!(($t::from_linear(x).to_linear() - x).abs() < $f::EPSILON);
+                    assert_eq!($t::from_ln(-2000.0).to_linear(), 0.0);
+                    assert_eq!($t::from_ln(2000.0).to_linear(), $f::INFINITY);
+                }
+
+                #[test]
+                fn sum_and_product() {
+                    let v: Vec<$f> = vec![0.5, -1.0, 2.25, $f::NEG_INFINITY];
+                    let w: Vec<$t> = v.iter().map(|x| $t::from_ln(*x)).collect();
+
+                    assert_eq!(w.iter().sum::<$t>().ln(), v.iter().ln_sum_exp());
+                    assert_eq!(w.clone().into_iter().sum::<$t>(), w.iter().sum::<$t>());
+                    assert_eq!(w[..3].iter().product::<$t>().ln(), 0.5 + -1.0 + 2.25);
+                    assert_eq!(w.iter().product::<$t>(), $t::ZERO);
+                    assert_eq!(w.into_iter().product::<$t>(), $t::ZERO);
+
+                    let w: Vec<$t> = vec![];
+                    assert_eq!(w.iter().sum::<$t>(), $t::ZERO);
+                    assert_eq!(w.iter().product::<$t>(), $t::ONE);
+                    // exactly `ONE`, i.e. +0.0 rather than the -0.0 of an empty float sum
+                    assert!(w.iter().product::<$t>().ln().is_sign_positive());
+                    assert!(w.into_iter().product::<$t>().ln().is_sign_positive());
+                }
+
+                #[cfg(feature = "num-traits")]
+                #[test]
+                fn num_traits_works() {
+                    use num_traits::{Inv, One, Pow, Zero};
+
+                    fn dot<T: Zero + One + Copy>(a: &[T], b: &[T]) -> T {
+                        a.iter().zip(b.iter()).fold(T::zero(), |acc, (x, y)| acc + *x * *y)
+                    }
+                    let a: Vec<$t> = vec![$t::from_linear(0.5), $t::from_linear(0.25)];
+                    let b: Vec<$t> = vec![$t::from_linear(0.25), $t::from_linear(1.0)];
+                    assert!((dot(&a, &b).to_linear() - 0.375).abs() < $f::EPSILON);
+
+                    assert_eq!(<$t as Zero>::zero(), $t::ZERO);
+                    assert!(Zero::is_zero(&$t::ZERO));
+                    assert_eq!(<$t as One>::one(), $t::ONE);
+                    assert!($t::ONE.is_one());
+                    assert_eq!($t::from_ln(0.5).inv(), $t::from_ln(-0.5));
+                    assert_eq!($t::from_ln(0.5).pow(3.0), $t::from_ln(1.5));
+                    assert_eq!($t::ZERO.pow(0.0), $t::ONE);
+                    assert_eq!($t::ZERO.pow(2.0), $t::ZERO);
+                }
+            }
+        }
+    }
+    log_float_tests! { log_f64_impl LogF64 f64 }
+    log_float_tests! { log_f32_impl LogF32 f32 }
+}
@@ -0,0 +1,159 @@
+//! Floating point functions, backed by `std` or, for `no_std` builds, by `libm`.
+//!
+//! The library calls these functions, e.g. `math::exp(x)`, rather than the inherent
+//! methods of `f64` and `f32`. As the test harness links `std`, whose inherent methods
+//! would otherwise take precedence, this ensures that the tests exercise the same
+//! backend as the library built for users, under either configuration.
+//! Likewise, `LnExp` provides, for `no_std` builds, the subset of
+//! [`lnexp::LnExp`](https://docs.rs/lnexp/0.2.0/lnexp/trait.LnExp.html) used by this
+//! crate, following the same case analysis.
+
+pub(crate) trait Float: Copy {
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    // Only `LnExp` requires these; with `std`, it is provided by `lnexp`.
+    #[cfg(not(feature = "std"))]
+    fn exp_m1(self) -> Self;
+    #[cfg(not(feature = "std"))]
+    fn ln_1p(self) -> Self;
+}
+
+#[inline]
+pub(crate) fn exp<T: Float>(x: T) -> T {
+    x.exp()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(crate) fn exp_m1<T: Float>(x: T) -> T {
+    x.exp_m1()
+}
+
+#[inline]
+pub(crate) fn ln<T: Float>(x: T) -> T {
+    x.ln()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(crate) fn ln_1p<T: Float>(x: T) -> T {
+    x.ln_1p()
+}
+
+#[cfg(feature = "std")]
+macro_rules! impl_float {
+    { $($f:ident $exp:ident $expm1:ident $log:ident $log1p:ident),+ } => {
+        $(
+            impl Float for $f {
+                #[inline]
+                fn exp(self) -> Self {
+                    $f::exp(self)
+                }
+                #[inline]
+                fn ln(self) -> Self {
+                    $f::ln(self)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+macro_rules! impl_float {
+    { $($f:ident $exp:ident $expm1:ident $log:ident $log1p:ident),+ } => {
+        $(
+            impl Float for $f {
+                #[inline]
+                fn exp(self) -> Self {
+                    libm::$exp(self)
+                }
+                #[inline]
+                fn exp_m1(self) -> Self {
+                    libm::$expm1(self)
+                }
+                #[inline]
+                fn ln(self) -> Self {
+                    libm::$log(self)
+                }
+                #[inline]
+                fn ln_1p(self) -> Self {
+                    libm::$log1p(self)
+                }
+            }
+        )+
+    };
+}
+
+// Without a backend, the crate fails to compile by `compile_error!`; this implementation
+// only prevents follow-on errors from obscuring that message.
+#[cfg(not(any(feature = "std", feature = "libm")))]
+macro_rules! impl_float {
+    { $($f:ident $exp:ident $expm1:ident $log:ident $log1p:ident),+ } => {
+        $(
+            impl Float for $f {
+                fn exp(self) -> Self {
+                    unreachable!()
+                }
+                fn exp_m1(self) -> Self {
+                    unreachable!()
+                }
+                fn ln(self) -> Self {
+                    unreachable!()
+                }
+                fn ln_1p(self) -> Self {
+                    unreachable!()
+                }
+            }
+        )+
+    };
+}
+impl_float! { f64 exp expm1 log log1p, f32 expf expm1f logf log1pf }
+
+#[cfg(not(feature = "std"))]
+pub(crate) trait LnExp {
+    fn ln_1m_exp(&self) -> Self;
+    // With `num-traits`, `LogAddExp` is provided by the generic implementation.
+    #[cfg(not(feature = "num-traits"))]
+    fn ln_1p_exp(&self) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! impl_lnexp {
+    { $($f:ident $ln_of_p_mul_ln_2:expr),+ } => {
+        $(
+            impl LnExp for $f {
+                // See: Martin Maechler (2012), Accurately Computing log(1 − exp(− |a|))
+                // http://cran.r-project.org/web/packages/Rmpfr/vignettes/log1mexp-note.pdf
+                fn ln_1m_exp(&self) -> $f {
+                    if *self < -core::$f::consts::LN_2 {
+                        ln_1p(-exp(*self))
+                    } else {
+                        ln(-exp_m1(*self))
+                    }
+                }
+
+                // See Section 3 of Maechler (2012), with the improvements of
+                // https://github.com/JuliaStats/LogExpFunctions.jl/files/8218470/log1pexp.pdf
+                #[cfg(not(feature = "num-traits"))]
+                fn ln_1p_exp(&self) -> $f {
+                    const PRECISION: $f = $f::MANTISSA_DIGITS as $f;
+                    const P_LN_2: $f = PRECISION * core::$f::consts::LN_2;
+                    const X0: $f = -P_LN_2;
+                    const X1: $f = (PRECISION - 1.0) * core::$f::consts::LN_2 / 2.0;
+                    const X2: $f = P_LN_2 + $ln_of_p_mul_ln_2 * (1.0 / P_LN_2 - 1.0);
+                    if *self <= X0 {
+                        exp(*self)
+                    } else if *self <= X1 {
+                        ln_1p(exp(*self))
+                    } else if *self <= X2 {
+                        *self + exp(-*self)
+                    } else {
+                        *self
+                    }
+                }
+            }
+        )+
+    };
+}
+#[cfg(not(feature = "std"))]
+impl_lnexp! { f64 3.6037789929704576, f32 2.8115408 }
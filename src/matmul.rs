@@ -0,0 +1,298 @@
+use crate::math::exp;
+use crate::OnlineLogSumExp;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// The dimensions of the tiles of the output (rows by columns) and of the inner dimension.
+// A tile of `B` is `BLOCK_K * BLOCK_J` elements, i.e. 32KiB of `f64`.
+const BLOCK_I: usize = 16;
+const BLOCK_J: usize = 64;
+const BLOCK_K: usize = 64;
+
+/// A trait for the product of matrices in the log semiring, i.e. the log of the product
+/// of the matrices of exponentials, `C[i,j] = ln(sum_k(exp(A[i,k] + B[k,j])))`, for
+/// row-major matrices held in slices; e.g. the product of transition matrices in log
+/// space, or a step of the forward algorithm.
+///
+/// Each element is evaluated by the online technique of [`LogSumExp`](crate::LogSumExp),
+/// applied to blocks of the inner dimension: within a block, the maximum is found, then
+/// the exponentials are summed relative to it, at the cost of one `exp` per term, and the
+/// (maximum, scaled sum) pairs of the blocks are combined as by
+/// [`OnlineLogSumExp::merge`]. The output is computed in tiles, so that the tile of `B`
+/// remains in cache across the rows of the tile of `A`. The result agrees with
+/// `LogSumExp` of the terms to within a few ulp, and the special values are handled
+/// identically: structural zeros, `-inf`, contribute nothing, and an element of the
+/// output for which every term is `-inf` is `-inf`; a term `+inf` saturates the element,
+/// and `nan`, including `-inf + inf`, is absorbing.
+///
+/// # Panics
+/// The methods panic if the lengths of the slices do not match the dimensions.
+pub trait LogMatMul {
+    type Output;
+
+    /// Return the `m` by `n` log-product of `self`, an `m` by `k` matrix, and `rhs`, a
+    /// `k` by `n` matrix.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogMatMul, LogSumExp};
+    ///
+    /// let a: Vec<f64> = vec![0.5, -1.0, f64::NEG_INFINITY, 2.0, 0.0, 1.0];
+    /// let b: Vec<f64> = vec![1.0, 0.0, -2.0, f64::NEG_INFINITY, 0.5, 0.25];
+    /// // a is 2 by 3, b is 3 by 2
+    /// let c = a.log_matmul(&b, 2, 3, 2);
+    /// assert!((c[0] - [1.5, -3.0, f64::NEG_INFINITY].iter().ln_sum_exp()).abs() < 1e-15);
+    /// assert!((c[3] - [2.0, f64::NEG_INFINITY, 1.25].iter().ln_sum_exp()).abs() < 1e-15);
+    ///
+    /// // the product of probability matrices
+    /// let p: Vec<f64> = [0.9_f64, 0.1, 0.2, 0.8].iter().map(|x| x.ln()).collect();
+    /// let p2 = p.log_matmul(&p, 2, 2, 2);
+    /// assert!((p2[0].exp() - (0.9 * 0.9 + 0.1 * 0.2)).abs() < 1e-15);
+    /// ```
+    fn log_matmul(&self, rhs: &Self, m: usize, k: usize, n: usize) -> Self::Output;
+
+    /// Write the `m` by `n` log-product of `self`, an `m` by `k` matrix, and `rhs`, a `k`
+    /// by `n` matrix, to `out`.
+    fn log_matmul_into(&self, rhs: &Self, out: &mut Self, m: usize, k: usize, n: usize);
+
+    /// Return the log-product of `self`, an `m` by `k` matrix, and `x`, a vector of
+    /// length `k`, i.e. `y[i] = ln(sum_k(exp(A[i,k] + x[k])))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogMatMul, LogSumExp};
+    ///
+    /// let a: Vec<f64> = vec![0.5, -1.0, f64::NEG_INFINITY, 2.0, 0.0, 1.0];
+    /// let x: Vec<f64> = vec![1.0, -2.0, 0.5];
+    /// let y = a.log_matvec(&x, 2, 3);
+    /// assert!((y[1] - [3.0_f64, -2.0, 1.5].iter().ln_sum_exp()).abs() < 1e-15);
+    /// ```
+    fn log_matvec(&self, x: &Self, m: usize, k: usize) -> Self::Output;
+
+    /// Return the log-product of `x`, a vector of length `m`, and `self`, an `m` by `k`
+    /// matrix, i.e. `y[j] = ln(sum_i(exp(x[i] + A[i,j])))`, e.g. a step of the forward
+    /// algorithm for a hidden Markov model with transition matrix `self`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogMatMul, LogSumExp};
+    ///
+    /// let a: Vec<f64> = vec![0.5, -1.0, f64::NEG_INFINITY, 2.0, 0.0, 1.0];
+    /// let x: Vec<f64> = vec![1.0, -2.0];
+    /// let y = a.log_vecmat(&x, 2, 3);
+    /// assert!((y[2] - [f64::NEG_INFINITY, -1.0].iter().ln_sum_exp()).abs() < 1e-15);
+    /// ```
+    fn log_vecmat(&self, x: &Self, m: usize, k: usize) -> Self::Output;
+}
+
+macro_rules! impl_log_matmul {
+    { $($f:ident)+ } => {
+        $(
+            impl LogMatMul for [$f] {
+                type Output = Vec<$f>;
+
+                fn log_matmul(&self, rhs: &Self, m: usize, k: usize, n: usize) -> Self::Output {
+                    let mut out = vec![$f::NEG_INFINITY; m * n];
+                    self.log_matmul_into(rhs, &mut out, m, k, n);
+                    out
+                }
+
+                fn log_matmul_into(&self, rhs: &Self, out: &mut Self, m: usize, k: usize, n: usize) {
+                    assert_eq!(self.len(), m * k, "lhs must be m by k");
+                    assert_eq!(rhs.len(), k * n, "rhs must be k by n");
+                    assert_eq!(out.len(), m * n, "out must be m by n");
+
+                    let mut acc = [OnlineLogSumExp::<$f>::new(); BLOCK_I * BLOCK_J];
+                    let mut block_max = [0.0; BLOCK_J];
+                    let mut block_sum = [0.0; BLOCK_J];
+                    for i0 in (0..m).step_by(BLOCK_I) {
+                        let bi = BLOCK_I.min(m - i0);
+                        for j0 in (0..n).step_by(BLOCK_J) {
+                            let bj = BLOCK_J.min(n - j0);
+                            acc.iter_mut().for_each(|s| *s = OnlineLogSumExp::<$f>::new());
+                            for k0 in (0..k).step_by(BLOCK_K) {
+                                let bk = BLOCK_K.min(k - k0);
+                                for i in 0..bi {
+                                    let a = &self[(i0 + i) * k + k0..][..bk];
+                                    let (max, sum) = (&mut block_max[..bj], &mut block_sum[..bj]);
+
+                                    // The maximum of the terms of the block, propagating nan.
+                                    max.fill($f::NEG_INFINITY);
+                                    for (kk, a) in a.iter().enumerate() {
+                                        let b = &rhs[(k0 + kk) * n + j0..][..bj];
+                                        for (mx, b) in max.iter_mut().zip(b.iter()) {
+                                            let x = a + b;
+                                            if x > *mx || x.is_nan() {
+                                                *mx = x;
+                                            }
+                                        }
+                                    }
+
+                                    // The sum of the exponentials relative to the maximum,
+                                    // which is only meaningful if the maximum is finite.
+                                    sum.fill(0.0);
+                                    for (kk, a) in a.iter().enumerate() {
+                                        let b = &rhs[(k0 + kk) * n + j0..][..bj];
+                                        for ((s, mx), b) in sum.iter_mut().zip(max.iter()).zip(b.iter()) {
+                                            if mx.is_finite() {
+                                                *s += exp(a + b - mx);
+                                            }
+                                        }
+                                    }
+
+                                    let acc = &mut acc[i * BLOCK_J..][..bj];
+                                    for ((s, mx), sum) in acc.iter_mut().zip(max.iter()).zip(sum.iter()) {
+                                        s.merge(&OnlineLogSumExp::<$f>::from_parts(*mx, *sum));
+                                    }
+                                }
+                            }
+                            for i in 0..bi {
+                                let row = &mut out[(i0 + i) * n + j0..][..bj];
+                                for (c, s) in row.iter_mut().zip(acc[i * BLOCK_J..].iter()) {
+                                    *c = s.finish();
+                                }
+                            }
+                        }
+                    }
+                }
+
+                fn log_matvec(&self, x: &Self, m: usize, k: usize) -> Self::Output {
+                    assert_eq!(self.len(), m * k, "matrix must be m by k");
+                    assert_eq!(x.len(), k, "vector must be of length k");
+                    if k == 0 {
+                        return vec![$f::NEG_INFINITY; m];
+                    }
+                    self.chunks_exact(k)
+                        .map(|row| {
+                            let mut acc = OnlineLogSumExp::<$f>::new();
+                            for (a, x) in row.chunks(BLOCK_K).zip(x.chunks(BLOCK_K)) {
+                                let mut max = $f::NEG_INFINITY;
+                                for (a, x) in a.iter().zip(x.iter()) {
+                                    let y = a + x;
+                                    if y > max || y.is_nan() {
+                                        max = y;
+                                    }
+                                }
+                                let mut sum = 0.0;
+                                if max.is_finite() {
+                                    for (a, x) in a.iter().zip(x.iter()) {
+                                        sum += exp(a + x - max);
+                                    }
+                                }
+                                acc.merge(&OnlineLogSumExp::<$f>::from_parts(max, sum));
+                            }
+                            acc.finish()
+                        })
+                        .collect()
+                }
+
+                fn log_vecmat(&self, x: &Self, m: usize, k: usize) -> Self::Output {
+                    x.log_matmul(self, 1, m, k)
+                }
+            }
+        )+
+    }
+}
+impl_log_matmul! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::LogSumExp;
+
+    macro_rules! log_matmul_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn naive(a: &[$f], b: &[$f], m: usize, k: usize, n: usize) -> Vec<$f> {
+                    let mut c = Vec::with_capacity(m * n);
+                    for i in 0..m {
+                        for j in 0..n {
+                            c.push((0..k).map(|kk| a[i * k + kk] + b[kk * n + j]).ln_sum_exp());
+                        }
+                    }
+                    c
+                }
+
+                fn assert_close(lhs: &[$f], rhs: &[$f]) {
+                    assert_eq!(lhs.len(), rhs.len());
+                    for (x, y) in lhs.iter().zip(rhs.iter()) {
+                        if y.is_nan() {
+                            assert!(x.is_nan());
+                        } else if !y.is_finite() {
+                            assert_eq!(x, y);
+                        } else {
+                            assert!((x - y).abs() <= 8.0 * $f::EPSILON * y.abs().max(1.0), "{} {}", x, y);
+                        }
+                    }
+                }
+
+                #[test]
+                fn log_matmul_works() {
+                    // dimensions which are, and are not, multiples of the blocks
+                    for &(m, k, n) in &[(1, 1, 1), (2, 3, 2), (16, 64, 64), (37, 70, 131), (5, 200, 3), (0, 4, 3), (3, 0, 4)] {
+                        let a = values::<$f>(m * k, -50.0, 10.0);
+                        let mut b = values::<$f>(k * n, -10.0, 50.0);
+                        b.reverse();
+                        let c = a.log_matmul(&b, m, k, n);
+                        assert_close(&c, &naive(&a, &b, m, k, n));
+
+                        let mut out = vec![0.0; m * n];
+                        a.log_matmul_into(&b, &mut out, m, k, n);
+                        assert_eq!(out, c);
+                    }
+                }
+
+                #[test]
+                fn log_matvec_works() {
+                    for &(m, k) in &[(1, 1), (3, 2), (17, 130), (4, 0)] {
+                        let a = values::<$f>(m * k, -50.0, 10.0);
+                        let x = values::<$f>(k, -5.0, 5.0);
+                        assert_close(&a.log_matvec(&x, m, k), &naive(&a, &x, m, k, 1));
+                        let x = values::<$f>(m, -5.0, 5.0);
+                        assert_close(&a.log_vecmat(&x, m, k), &naive(&x, &a, 1, m, k));
+                    }
+                }
+
+                #[test]
+                fn special_values() {
+                    let (m, k, n) = (3, 150, 5);
+                    let mut a = values::<$f>(m * k, -5.0, 5.0);
+                    let mut b = values::<$f>(k * n, -5.0, 5.0);
+                    // structural zeros: a row of a, a column of b, and scattered entries
+                    a[k..2 * k].fill($f::NEG_INFINITY);
+                    (0..k).for_each(|kk| b[kk * n + 1] = $f::NEG_INFINITY);
+                    a.iter_mut().step_by(3).for_each(|x| *x = $f::NEG_INFINITY);
+                    b.iter_mut().step_by(7).for_each(|x| *x = $f::NEG_INFINITY);
+                    let c = a.log_matmul(&b, m, k, n);
+                    assert_close(&c, &naive(&a, &b, m, k, n));
+                    assert!(c[n..2 * n].iter().all(|x| *x == $f::NEG_INFINITY));
+                    assert_eq!(c[1], $f::NEG_INFINITY);
+                    assert!(c.iter().all(|x| !x.is_nan()));
+
+                    // +inf, nan, and -inf + inf, in different blocks of the inner dimension
+                    a[2] = $f::INFINITY;
+                    a[2 * k + 100] = $f::NAN;
+                    b[(k - 1) * n + 4] = $f::INFINITY;
+                    a[k - 1] = $f::NEG_INFINITY;
+                    let c = a.log_matmul(&b, m, k, n);
+                    assert_close(&c, &naive(&a, &b, m, k, n));
+                    let x: Vec<$f> = (0..k).map(|kk| b[kk * n + 4]).collect();
+                    assert_close(&a.log_matvec(&x, m, k), &naive(&a, &x, m, k, 1));
+                }
+
+                #[test]
+                #[should_panic]
+                fn shapes_are_checked() {
+                    let a = values::<$f>(6, -1.0, 1.0);
+                    a.log_matmul(&a, 2, 3, 3);
+                }
+            }
+        }
+    }
+    log_matmul_tests! { f64_log_matmul_impl f64 }
+    log_matmul_tests! { f32_log_matmul_impl f32 }
+}
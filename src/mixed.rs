@@ -0,0 +1,157 @@
+use crate::{LogAddExp, LogSumExp};
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// sequence of `f32` with the running maximum and scaled sum of the 1-pass (online)
+/// algorithm of [`LogSumExp`] held in `f64`, returning the `f64` result. Each item is
+/// widened to `f64` as it is consumed, hence, no `f64` copy of the sequence is
+/// materialized. The handling of +/-infinity and `nan` is identical to that of
+/// `LogSumExp`.
+///
+/// The scaled sum of `LogSumExp<f32>` is held in `f32`; once it exceeds `2^24` times the
+/// largest term, terms of similar magnitude no longer increase it. With the sum held in
+/// `f64`, the error is that of `LogSumExp<f64>` for the widened inputs, which is
+/// negligible at the precision of `f32` for any practical length of sequence.
+pub trait MixedLogSumExp<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the sequence,
+    /// accumulated in `f64`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, MixedLogSumExp};
+    ///
+    /// // 2^25 terms of exp(0)
+    /// let n = 1_usize << 25;
+    /// let lhs = std::iter::repeat_n(0.0_f32, n).ln_sum_exp_f64();
+    /// assert!((lhs - 25.0 * std::f64::consts::LN_2).abs() < 1e-12);
+    /// // the f32 sum saturates at 2^24
+    /// let rhs = std::iter::repeat_n(0.0_f32, n).ln_sum_exp();
+    /// assert_eq!(rhs, 24.0 * std::f32::consts::LN_2);
+    ///
+    /// let v: Vec<f32> = vec![f32::NEG_INFINITY, 0.5];
+    /// assert_eq!(v.iter().ln_sum_exp_f64(), 0.5);
+    /// ```
+    fn ln_sum_exp_f64(self) -> Self::Output;
+}
+
+impl<U> MixedLogSumExp<f32, U> for U
+where
+    U: Iterator<Item = f32>,
+{
+    type Output = f64;
+    fn ln_sum_exp_f64(self) -> Self::Output {
+        self.map(f64::from).ln_sum_exp()
+    }
+}
+
+impl<'a, U> MixedLogSumExp<&'a f32, U> for U
+where
+    U: Iterator<Item = &'a f32>,
+{
+    type Output = f64;
+    fn ln_sum_exp_f64(self) -> Self::Output {
+        self.map(|x| f64::from(*x)).ln_sum_exp()
+    }
+}
+
+// `ln(exp(a) + exp(b))` for an `f32` and an `f64`, evaluated in `f64`.
+macro_rules! impl_mixed_logaddexp {
+    { $($lhs:ident $rhs:ident)+ } => {
+        $(
+            impl LogAddExp<$rhs> for $lhs {
+                type Output = f64;
+                fn ln_add_exp(&self, rhs: $rhs) -> Self::Output {
+                    f64::from(*self).ln_add_exp(f64::from(rhs))
+                }
+            }
+            impl LogAddExp<&$rhs> for $lhs {
+                type Output = f64;
+                fn ln_add_exp(&self, rhs: &$rhs) -> Self::Output {
+                    self.ln_add_exp(*rhs)
+                }
+            }
+        )+
+    }
+}
+impl_mixed_logaddexp! { f32 f64 f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn ln_sum_exp_f64_works() {
+        let v: Vec<f32> = vec![0.5, 1.0, -3.0, 2.25];
+        let w: Vec<f64> = v.iter().map(|&x| f64::from(x)).collect();
+        assert_eq!(v.iter().ln_sum_exp_f64(), w.iter().ln_sum_exp());
+        assert_eq!(v.into_iter().ln_sum_exp_f64(), w.into_iter().ln_sum_exp());
+
+        // The f32 sum saturates, whereas the f64 sum does not.
+        let n = (1_usize << 24) + (1 << 22);
+        let rhs = (n as f64).ln() - 1.0;
+        let lhs = core::iter::repeat_n(-1.0_f32, n).ln_sum_exp_f64();
+        assert!((lhs - rhs).abs() <= 2.0 * f64::EPSILON * rhs);
+        let lhs = core::iter::repeat_n(-1.0_f32, n).ln_sum_exp();
+        assert!((f64::from(lhs) - rhs).abs() > 0.1);
+    }
+
+    #[test]
+    fn ln_sum_exp_f64_special_values() {
+        let inf = f32::INFINITY;
+        let neg_inf = f32::NEG_INFINITY;
+        let nan = f32::NAN;
+        let x: f32 = 0.5;
+
+        let cases: Vec<Vec<f32>> = vec![
+            vec![],
+            vec![neg_inf, x],
+            vec![neg_inf; 3],
+            vec![x, inf, neg_inf],
+            vec![inf, inf],
+            vec![x, inf, nan],
+            vec![nan, x],
+            vec![x, nan, inf],
+        ];
+        for v in cases {
+            let lhs = v.iter().ln_sum_exp_f64();
+            let rhs = f64::from(v.iter().ln_sum_exp());
+            assert!(lhs == rhs || (lhs.is_nan() && rhs.is_nan()), "{:?}", v);
+        }
+    }
+
+    #[test]
+    fn mixed_ln_add_exp_works() {
+        let x: f32 = 0.5;
+        let y: f64 = 1.25;
+        let rhs = f64::from(x).ln_add_exp(y);
+        assert_eq!(x.ln_add_exp(y), rhs);
+        assert_eq!(x.ln_add_exp(&y), rhs);
+        assert_eq!(y.ln_add_exp(x), rhs);
+        assert_eq!(y.ln_add_exp(&x), rhs);
+
+        // Values for which f32 overflows.
+        let x: f32 = 100.0;
+        let y: f64 = 700.0;
+        assert_eq!(x.ln_add_exp(y), 700.0);
+        assert_eq!(y.ln_add_exp(x), 700.0);
+
+        assert_eq!(
+            f32::NEG_INFINITY.ln_add_exp(f64::NEG_INFINITY),
+            f64::NEG_INFINITY
+        );
+        assert_eq!(f32::INFINITY.ln_add_exp(-1.0_f64), f64::INFINITY);
+        assert!(f32::NAN.ln_add_exp(f64::INFINITY).is_nan());
+        assert!(1.0_f64.ln_add_exp(f32::NAN).is_nan());
+    }
+
+    #[test]
+    fn unsuffixed_literal_promotes_to_f64() {
+        let x: f32 = 0.5;
+        let z = x.ln_add_exp(1.0);
+        let _: f64 = z;
+        assert_eq!(z, f64::from(x).ln_add_exp(1.0_f64));
+    }
+}
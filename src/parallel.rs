@@ -0,0 +1,188 @@
+use crate::simd::SliceLogSumExpState;
+use crate::OnlineLogSumExp;
+use rayon::prelude::*;
+
+// The number of elements of a slice which are reduced by the vectorized kernel in each task.
+const CHUNK_LEN: usize = 4096;
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of
+/// a [`ParallelIterator`], in which each thread accumulates an [`OnlineLogSumExp`] state
+/// (running maximum and scaled sum) and the partial states are merged. The handling of
+/// +/-infinity and `nan` is identical to the serial [`LogSumExp`](crate::LogSumExp).
+pub trait ParallelLogSumExp<T> {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the
+    /// parallel iterator.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, ParallelLogSumExp};
+    /// use rayon::prelude::*;
+    ///
+    /// let v: Vec<f64> = (1..100_000).map(|x| (x as f64).ln()).collect();
+    /// let rhs = v.iter().ln_sum_exp();
+    /// assert!((v.par_iter().par_ln_sum_exp() - rhs).abs() < 1e-12);
+    /// assert!((v.into_par_iter().par_ln_sum_exp() - rhs).abs() < 1e-12);
+    ///
+    /// let v: Vec<f64> = vec![0.5, f64::INFINITY, f64::NAN];
+    /// assert!(v.par_iter().par_ln_sum_exp().is_nan());
+    /// ```
+    fn par_ln_sum_exp(self) -> Self::Output;
+}
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of
+/// a slice in parallel, in which contiguous chunks are reduced by the vectorized kernel
+/// of [`SliceLogSumExp`](crate::SliceLogSumExp) to the state of the online algorithm,
+/// and the per-chunk states are merged as in [`ParallelLogSumExp`].
+/// The handling of +/-infinity and `nan` is identical to the serial
+/// [`LogSumExp`](crate::LogSumExp).
+pub trait ParallelSliceLogSumExp {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the slice.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, ParallelSliceLogSumExp};
+    ///
+    /// let v: Vec<f64> = (1..100_000).map(|x| (x as f64).ln()).collect();
+    /// assert!((v.par_ln_sum_exp() - v.iter().ln_sum_exp()).abs() < 1e-12);
+    /// ```
+    fn par_ln_sum_exp(&self) -> Self::Output;
+}
+
+macro_rules! impl_parallel_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> ParallelLogSumExp<$f> for U
+            where
+                U: ParallelIterator<Item = $f>,
+            {
+                type Output = $f;
+                fn par_ln_sum_exp(self) -> Self::Output {
+                    self.fold(OnlineLogSumExp::<$f>::new, |mut acc, x| {
+                        acc.push(x);
+                        acc
+                    })
+                    .reduce(OnlineLogSumExp::<$f>::new, |mut lhs, rhs| {
+                        lhs.merge(&rhs);
+                        lhs
+                    })
+                    .finish()
+                }
+            }
+
+            impl<'a, U> ParallelLogSumExp<&'a $f> for U
+            where
+                U: ParallelIterator<Item = &'a $f>,
+            {
+                type Output = $f;
+                fn par_ln_sum_exp(self) -> Self::Output {
+                    self.copied().par_ln_sum_exp()
+                }
+            }
+
+            impl ParallelSliceLogSumExp for [$f] {
+                type Output = $f;
+                fn par_ln_sum_exp(&self) -> Self::Output {
+                    self.par_chunks(CHUNK_LEN)
+                        .map(|chunk| chunk.ln_sum_exp_state())
+                        .reduce(OnlineLogSumExp::<$f>::new, |mut lhs, rhs| {
+                            lhs.merge(&rhs);
+                            lhs
+                        })
+                        .finish()
+                }
+            }
+        )+
+    }
+}
+impl_parallel_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+
+    macro_rules! parallel_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn assert_same(lhs: $f, rhs: $f, n: usize) {
+                    if rhs.is_finite() {
+                        let tol = (n as $f).sqrt().max(1.0) * 4.0 * $f::EPSILON * rhs.abs().max(1.0);
+                        assert!((lhs - rhs).abs() < tol, "{} {}", lhs, rhs);
+                    } else if rhs.is_nan() {
+                        assert!(lhs.is_nan());
+                    } else {
+                        assert_eq!(lhs, rhs);
+                    }
+                }
+
+                #[test]
+                fn par_ln_sum_exp_works() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    let cases: Vec<Vec<$f>> = vec![
+                        vec![],
+                        vec![x],
+                        vec![neg_inf],
+                        vec![inf],
+                        vec![nan],
+                        vec![neg_inf, x, y, neg_inf],
+                        vec![inf, x, y, neg_inf],
+                        vec![neg_inf; 4],
+                        vec![inf; 4],
+                        vec![inf, neg_inf, neg_inf, inf],
+                        vec![x, inf, nan, y],
+                        vec![nan, x, y, inf],
+                        vec![neg_inf, neg_inf, neg_inf, nan],
+                    ];
+                    for v in cases {
+                        let rhs = v.iter().ln_sum_exp();
+                        assert_same(v.par_iter().par_ln_sum_exp(), rhs, v.len());
+                        assert_same(v.par_ln_sum_exp(), rhs, v.len());
+                        assert_same(v.clone().into_par_iter().par_ln_sum_exp(), rhs, v.len());
+                    }
+                }
+
+                #[test]
+                fn par_ln_sum_exp_large() {
+                    let n: usize = 3 * CHUNK_LEN + 17;
+                    let v: Vec<$f> = (0..n).map(|i| ((i % 97) as $f) / 7.0 - 5.0).collect();
+                    let rhs = v.iter().ln_sum_exp();
+                    assert_same(v.par_iter().par_ln_sum_exp(), rhs, n);
+                    assert_same(v.par_ln_sum_exp(), rhs, n);
+
+                    for &special in [$f::INFINITY, $f::NEG_INFINITY, $f::NAN].iter() {
+                        for &i in [0, CHUNK_LEN - 1, CHUNK_LEN, n / 2, n - 1].iter() {
+                            let mut w = v.clone();
+                            w[i] = special;
+                            let rhs = w.iter().ln_sum_exp();
+                            assert_same(w.par_iter().par_ln_sum_exp(), rhs, n);
+                            assert_same(w.par_ln_sum_exp(), rhs, n);
+                        }
+                    }
+
+                    let mut w = v.clone();
+                    w[CHUNK_LEN + 1] = $f::INFINITY;
+                    w[n - 2] = $f::NAN;
+                    assert!(w.par_iter().par_ln_sum_exp().is_nan());
+                    assert!(w.par_ln_sum_exp().is_nan());
+
+                    let w: Vec<$f> = vec![$f::NEG_INFINITY; n];
+                    assert_eq!(w.par_ln_sum_exp(), $f::NEG_INFINITY);
+                }
+            }
+        }
+    }
+    parallel_logsumexp_tests! { f64_parallel_logsumexp_impl f64 }
+    parallel_logsumexp_tests! { f32_parallel_logsumexp_impl f32 }
+}
@@ -0,0 +1,413 @@
+use crate::math;
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// sequence of `f64` to nearly correctly-rounded precision, for use as a reference in the
+/// validation of faster evaluations. The handling of +/-infinity and `nan` is identical to
+/// that of [`LogSumExp`](crate::LogSumExp).
+///
+/// The 1-pass (online) algorithm of `LogSumExp` is used, with the scaled sum carried in
+/// double-double arithmetic (approximately 106 bits of precision), as are the differences
+/// of the inputs, their exponentials and the final logarithm; the result is rounded to
+/// `f64` only at the end. The relative error of the double-double evaluation is of the
+/// order of `10^-30`, hence, the result is the correctly-rounded value except in the rare
+/// case that the exact value lies within that distance of the midpoint between adjacent
+/// `f64`. Terms smaller than the largest term by a factor of more than `exp(-745)` do not
+/// contribute. The evaluation is roughly thirty times slower than that of `LogSumExp`.
+pub trait PreciseLogSumExp<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the sequence,
+    /// evaluated in double-double arithmetic and rounded to `f64`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::PreciseLogSumExp;
+    ///
+    /// // ln(1024 * exp(0.1)) = 0.1 + 10 * ln(2)
+    /// let v: Vec<f64> = vec![0.1; 1024];
+    /// assert_eq!(v.iter().ln_sum_exp_precise(), 7.031471805599453);
+    ///
+    /// // handle +/- infinity and `nan`
+    /// let v: Vec<f64> = vec![f64::NEG_INFINITY, 0.5];
+    /// assert_eq!(v.into_iter().ln_sum_exp_precise(), 0.5);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5, f64::NAN];
+    /// assert!(v.iter().ln_sum_exp_precise().is_nan());
+    /// ```
+    fn ln_sum_exp_precise(self) -> Self::Output;
+}
+
+/// A trait which provides evaluation of `ln(exp(a) + exp(b))` for `f64` to nearly
+/// correctly-rounded precision, by the method of [`PreciseLogSumExp`]. The handling of
+/// +/-infinity and `nan` is identical to that of [`LogAddExp`](crate::LogAddExp).
+pub trait PreciseLogAddExp<Rhs = Self> {
+    type Output;
+
+    /// Return the log of the sum of exponentials of `self` and `rhs`, evaluated in
+    /// double-double arithmetic and rounded to `f64`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::PreciseLogAddExp;
+    ///
+    /// let x: f64 = 1.0;
+    /// assert_eq!(x.ln_add_exp_precise(x), 1.0 + std::f64::consts::LN_2);
+    /// assert_eq!(x.ln_add_exp_precise(&f64::NEG_INFINITY), 1.0);
+    /// assert!(x.ln_add_exp_precise(f64::NAN).is_nan());
+    /// ```
+    fn ln_add_exp_precise(&self, rhs: Rhs) -> Self::Output;
+}
+
+/// An unevaluated sum `hi + lo`, with `|lo| <= ulp(hi) / 2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+// ln(2) to double-double precision.
+const LN_2: DoubleDouble = DoubleDouble {
+    hi: core::f64::consts::LN_2,
+    lo: 2.3190468138462996e-17,
+};
+// 2^27 + 1, for the splitting of Dekker's product.
+const SPLITTER: f64 = 134217729.0;
+// 1.5 * 2^52; adding this rounds to the nearest integer.
+const SHIFT: f64 = 6755399441055744.0;
+// The arguments of `exp` are divided by 2^REDUCTION before evaluation of the series.
+const REDUCTION: i32 = 10;
+
+impl DoubleDouble {
+    const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+    const ONE: DoubleDouble = DoubleDouble { hi: 1.0, lo: 0.0 };
+
+    // Requires |a| >= |b|, or a == 0.
+    #[inline]
+    fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+        let hi = a + b;
+        DoubleDouble {
+            hi,
+            lo: b - (hi - a),
+        }
+    }
+
+    #[inline]
+    fn two_sum(a: f64, b: f64) -> DoubleDouble {
+        let hi = a + b;
+        let bb = hi - a;
+        DoubleDouble {
+            hi,
+            lo: (a - (hi - bb)) + (b - bb),
+        }
+    }
+
+    #[inline]
+    fn split(a: f64) -> (f64, f64) {
+        let t = SPLITTER * a;
+        let hi = t - (t - a);
+        (hi, a - hi)
+    }
+
+    #[inline]
+    fn two_prod(a: f64, b: f64) -> DoubleDouble {
+        let p = a * b;
+        let (a_hi, a_lo) = Self::split(a);
+        let (b_hi, b_lo) = Self::split(b);
+        DoubleDouble {
+            hi: p,
+            lo: ((a_hi * b_hi - p) + a_hi * b_lo + a_lo * b_hi) + a_lo * b_lo,
+        }
+    }
+
+    fn add(self, rhs: DoubleDouble) -> DoubleDouble {
+        let s = Self::two_sum(self.hi, rhs.hi);
+        let t = Self::two_sum(self.lo, rhs.lo);
+        let s = Self::quick_two_sum(s.hi, s.lo + t.hi);
+        Self::quick_two_sum(s.hi, s.lo + t.lo)
+    }
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+
+    fn mul(self, rhs: DoubleDouble) -> DoubleDouble {
+        let p = Self::two_prod(self.hi, rhs.hi);
+        Self::quick_two_sum(p.hi, p.lo + (self.hi * rhs.lo + self.lo * rhs.hi))
+    }
+
+    fn mul_f64(self, rhs: f64) -> DoubleDouble {
+        let p = Self::two_prod(self.hi, rhs);
+        Self::quick_two_sum(p.hi, p.lo + self.lo * rhs)
+    }
+
+    fn div_f64(self, rhs: f64) -> DoubleDouble {
+        let q = self.hi / rhs;
+        let p = Self::two_prod(q, rhs);
+        let r = (((self.hi - p.hi) - p.lo) + self.lo) / rhs;
+        Self::quick_two_sum(q, r)
+    }
+
+    // Multiplication by 2^k, for -2044 <= k <= 0; exact, unless the result is subnormal.
+    fn scale(self, k: i32) -> DoubleDouble {
+        let pow2 = |k: i32| f64::from_bits(((k + 1023) as u64) << 52);
+        let (k1, k2) = (k / 2, k - k / 2);
+        DoubleDouble {
+            hi: self.hi * pow2(k1) * pow2(k2),
+            lo: self.lo * pow2(k1) * pow2(k2),
+        }
+    }
+
+    // exp(x) for x <= 0.
+    fn exp(self) -> DoubleDouble {
+        if self.hi < -746.0 {
+            return Self::ZERO;
+        }
+        // x = k * ln(2) + r, with |r| <= ln(2) / 2
+        let k = (self.hi / LN_2.hi + SHIFT) - SHIFT;
+        let r = self.add(LN_2.mul_f64(k).neg()).scale(-REDUCTION);
+        // expm1(r), by its Taylor series
+        let mut term = r;
+        let mut s = r;
+        let mut i = 2.0;
+        while term.hi.abs() > 1e-36 {
+            term = term.mul(r).div_f64(i);
+            s = s.add(term);
+            i += 1.0;
+        }
+        // expm1(2r) = 2 expm1(r) + expm1(r)^2
+        for _ in 0..REDUCTION {
+            s = s.mul_f64(2.0).add(s.mul(s));
+        }
+        s.add(Self::ONE).scale(k as i32)
+    }
+
+    // ln(x) for x >= 1, by a Newton step from the `f64` approximation:
+    // y_1 = y_0 + x exp(-y_0) - 1
+    fn ln(self) -> DoubleDouble {
+        let y = DoubleDouble {
+            hi: math::ln(self.hi),
+            lo: 0.0,
+        };
+        y.add(self.mul(y.neg().exp())).add(Self::ONE.neg())
+    }
+
+    // The difference of `f64`, exactly.
+    fn diff(a: f64, b: f64) -> DoubleDouble {
+        Self::two_sum(a, -b)
+    }
+}
+
+impl<U> PreciseLogSumExp<f64, U> for U
+where
+    U: Iterator<Item = f64>,
+{
+    type Output = f64;
+    fn ln_sum_exp_precise(mut self) -> Self::Output {
+        let mut max = f64::NEG_INFINITY;
+        let mut sum = DoubleDouble::ZERO;
+        while let Some(x) = self.next() {
+            if x == f64::NEG_INFINITY {
+                continue;
+            } else if x == f64::INFINITY {
+                for x in self.by_ref() {
+                    if x.is_nan() {
+                        return x;
+                    }
+                }
+                return f64::INFINITY;
+            } else if x.is_nan() {
+                return x;
+            }
+            if x > max {
+                if max != f64::NEG_INFINITY {
+                    sum = sum.mul(DoubleDouble::diff(max, x).exp());
+                }
+                max = x;
+            }
+            sum = sum.add(DoubleDouble::diff(x, max).exp());
+        }
+        if max == f64::NEG_INFINITY {
+            return max;
+        }
+        DoubleDouble { hi: max, lo: 0.0 }.add(sum.ln()).hi
+    }
+}
+
+impl<'a, U> PreciseLogSumExp<&'a f64, U> for U
+where
+    U: Iterator<Item = &'a f64>,
+{
+    type Output = f64;
+    fn ln_sum_exp_precise(self) -> Self::Output {
+        self.copied().ln_sum_exp_precise()
+    }
+}
+
+impl PreciseLogAddExp for f64 {
+    type Output = f64;
+    fn ln_add_exp_precise(&self, rhs: Self) -> Self::Output {
+        [*self, rhs].into_iter().ln_sum_exp_precise()
+    }
+}
+impl PreciseLogAddExp<&f64> for f64 {
+    type Output = f64;
+    fn ln_add_exp_precise(&self, rhs: &f64) -> Self::Output {
+        self.ln_add_exp_precise(*rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    fn dd(x: f64) -> DoubleDouble {
+        DoubleDouble { hi: x, lo: 0.0 }
+    }
+
+    // The relative difference of double-double values.
+    fn rel_diff(lhs: DoubleDouble, rhs: DoubleDouble) -> f64 {
+        lhs.add(rhs.neg()).hi.abs() / rhs.hi.abs()
+    }
+
+    // The reference values were computed with 80 significant decimal digits, then
+    // rounded to double-double.
+    #[test]
+    fn exp_works() {
+        assert_eq!(dd(0.0).exp(), DoubleDouble::ONE);
+        assert_eq!(dd(-800.0).exp(), DoubleDouble::ZERO);
+        let cases = [
+            (-1e-10, 0.9999999999, 8.279037096265651e-18),
+            (-0.1, 0.9048374180359595, 5.055984668733208e-17),
+            (-0.5, 0.6065306597126334, -6.593178415491414e-19),
+            (-1.0, 0.36787944117144233, -1.2428753672788363e-17),
+            (-2.5, 0.0820849986238988, -4.8047346661059284e-18),
+            (-20.0, 2.061153622438558e-09, -4.19755767595054e-26),
+            (-300.0, 5.148200222412013e-131, 2.962376373372979e-147),
+        ];
+        for (x, hi, lo) in cases {
+            let rhs = DoubleDouble { hi, lo };
+            assert!(rel_diff(dd(x).exp(), rhs) < 1e-30, "{}", x);
+        }
+        // exp(-k ln(2)) = 2^-k
+        for k in [1, 2, 10, 100, 1000] {
+            let rhs = dd(2.0_f64.powi(-k));
+            assert!(
+                rel_diff(LN_2.mul_f64(-(k as f64)).exp(), rhs) < 1e-30,
+                "{}",
+                k
+            );
+        }
+    }
+
+    #[test]
+    fn ln_works() {
+        assert_eq!(dd(1.0).ln(), DoubleDouble::ZERO);
+        assert!(rel_diff(dd(2.0).ln(), LN_2) < 1e-31);
+        let cases = [
+            (3.0, 1.0986122886681098, -9.07129723500153e-17),
+            (10.0, core::f64::consts::LN_10, -2.1707562233822494e-16),
+            (1000.0, 6.907755278982137, 2.369515526854504e-16),
+            (123456789.0, 18.63140176616802, -2.0819353844153095e-16),
+        ];
+        for (x, hi, lo) in cases {
+            let rhs = DoubleDouble { hi, lo };
+            assert!(rel_diff(dd(x).ln(), rhs) < 1e-30, "{}", x);
+        }
+    }
+
+    // The expected results were computed from the exact (binary) values of the inputs,
+    // with 80 significant decimal digits, then rounded to the nearest `f64`.
+    #[test]
+    fn ln_sum_exp_precise_matches_exact() {
+        let cases: Vec<(Vec<f64>, f64)> = vec![
+            (vec![0.0; 3], 1.0986122886681098),
+            (vec![0.1; 1024], 7.031471805599453),
+            (vec![1.0, 2.0, 3.0], 3.40760596444438),
+            (vec![-1000.0, -1000.5, -999.25], -998.6853276752927),
+            (vec![700.0, 699.0, -700.0, 0.0], 700.3132616875182),
+            (vec![0.5, 0.5 + 2.0_f64.powi(-50)], 1.1931471805599458),
+            (
+                vec![f64::NEG_INFINITY, 1.5, f64::NEG_INFINITY, 2.5],
+                2.813261687518223,
+            ),
+            (vec![-40.0, 0.0, -40.0], 8.496708510583178e-18),
+            (
+                (1..=1000).map(|i| i as f64 * 1e-3).collect(),
+                7.4495800919283885,
+            ),
+            (
+                (1..=1000).map(|i| -(i as f64).ln()).collect(),
+                2.0129639231141705,
+            ),
+        ];
+        for (v, rhs) in cases {
+            assert_eq!(
+                v.iter().ln_sum_exp_precise(),
+                rhs,
+                "{:?}",
+                &v[..v.len().min(4)]
+            );
+            assert_eq!(v.into_iter().ln_sum_exp_precise(), rhs);
+        }
+    }
+
+    #[test]
+    fn ln_add_exp_precise_matches_exact() {
+        let cases: Vec<(f64, f64, f64)> = vec![
+            (0.0, 0.0, core::f64::consts::LN_2),
+            (1.0, -1.0, 1.1269280110429725),
+            (-3.0, 5.0, 5.000335406372896),
+            (100.0, 100.0 - 2.0_f64.powi(-40), 100.69314718055949),
+            (-745.0, -745.5, -744.5259230158199),
+            (0.0, -36.0, 2.319522830243569e-16),
+        ];
+        for (x, y, rhs) in cases {
+            assert_eq!(x.ln_add_exp_precise(y), rhs, "{} {}", x, y);
+            assert_eq!(y.ln_add_exp_precise(&x), rhs, "{} {}", y, x);
+        }
+    }
+
+    #[test]
+    fn special_values() {
+        let inf = f64::INFINITY;
+        let neg_inf = f64::NEG_INFINITY;
+        let nan = f64::NAN;
+        let x = 0.5;
+        let y = 1.0;
+
+        let cases: Vec<Vec<f64>> = vec![
+            vec![neg_inf, x, neg_inf, neg_inf],
+            vec![inf, x, y, neg_inf],
+            vec![x, inf, y, neg_inf],
+            vec![neg_inf; 4],
+            vec![inf; 4],
+            vec![inf, neg_inf, neg_inf, inf],
+            vec![],
+            vec![neg_inf],
+            vec![inf],
+            vec![x],
+            vec![x, inf, nan, y],
+            vec![nan, x, y, inf],
+            vec![neg_inf, neg_inf, nan],
+            vec![x, y, nan],
+            vec![nan],
+        ];
+        for v in cases {
+            let rhs = v.iter().ln_sum_exp();
+            let lhs = v.iter().ln_sum_exp_precise();
+            assert!(lhs == rhs || (lhs.is_nan() && rhs.is_nan()), "{:?}", v);
+        }
+
+        assert_eq!(inf.ln_add_exp_precise(neg_inf), inf);
+        assert_eq!(neg_inf.ln_add_exp_precise(neg_inf), neg_inf);
+        assert_eq!(neg_inf.ln_add_exp_precise(x), x);
+        assert!(nan.ln_add_exp_precise(inf).is_nan());
+        assert!(x.ln_add_exp_precise(nan).is_nan());
+    }
+}
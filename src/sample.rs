@@ -0,0 +1,311 @@
+use crate::Softmax;
+use alloc::vec;
+use alloc::vec::Vec;
+use rand::Rng;
+
+/// A trait for sampling from the categorical distribution defined by unnormalized log
+/// weights, `p_i = exp(x_i) / sum_j(exp(x_j))`, by the
+/// [Gumbel-max trick](https://en.wikipedia.org/wiki/Gumbel_distribution#Gumbel_reparametrization_tricks):
+/// the index of the maximum of `x_i + g_i`, where the `g_i` are independent standard
+/// Gumbel variates, is distributed as `p`. The log weights are never normalized, and the
+/// samples require a single pass, without LogSumExp or a walk of the cumulative
+/// distribution.
+///
+/// A log weight of `-inf` is a probability of zero, hence, its index is never sampled;
+/// if any log weight is `+inf`, the probability is shared equally by those indices, as
+/// in [`Softmax`]. If any log weight is `nan`, or all are `-inf`, there is no
+/// distribution from which to sample.
+pub trait GumbelSample {
+    type Output;
+
+    /// Return an index sampled from the categorical distribution, or `None` if there is
+    /// no distribution from which to sample.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::GumbelSample;
+    /// use rand::{rngs::SmallRng, SeedableRng};
+    ///
+    /// let mut rng = SmallRng::seed_from_u64(1);
+    /// let ln_w: Vec<f64> = vec![0.0, f64::NEG_INFINITY, 2.0_f64.ln()];
+    /// let mut counts = [0; 3];
+    /// for _ in 0..3000 {
+    ///     counts[ln_w.gumbel_max(&mut rng).unwrap()] += 1;
+    /// }
+    /// assert_eq!(counts[1], 0);
+    /// assert!((counts[2] as f64 / 3000.0 - 2.0 / 3.0).abs() < 0.05);
+    ///
+    /// let ln_w: Vec<f64> = vec![f64::NEG_INFINITY; 2];
+    /// assert_eq!(ln_w.gumbel_max(&mut rng), None);
+    /// ```
+    fn gumbel_max<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize>;
+
+    /// Return `k` distinct indices sampled without replacement from the categorical
+    /// distribution, in the order in which they were drawn, i.e. the indices of the `k`
+    /// largest of `x_i + g_i`, in descending order. If fewer than `k` indices have
+    /// non-zero probability, all of them are returned; if there is no distribution from
+    /// which to sample, none are.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::GumbelSample;
+    /// use rand::{rngs::SmallRng, SeedableRng};
+    ///
+    /// let mut rng = SmallRng::seed_from_u64(1);
+    /// let ln_w: Vec<f64> = vec![0.0, f64::NEG_INFINITY, 1.0, 2.0, f64::INFINITY];
+    /// let s = ln_w.gumbel_top_k(3, &mut rng);
+    /// assert_eq!(s.len(), 3);
+    /// assert_eq!(s[0], 4);
+    /// assert!(!s.contains(&1));
+    ///
+    /// assert_eq!(ln_w.gumbel_top_k(10, &mut rng).len(), 4);
+    /// ```
+    fn gumbel_top_k<R: Rng + ?Sized>(&self, k: usize, rng: &mut R) -> Vec<usize>;
+
+    /// Return a relaxed sample from the categorical distribution at temperature `tau`,
+    /// i.e. the [Gumbel-softmax](https://arxiv.org/abs/1611.01144) `softmax((x + g) / tau)`,
+    /// which approaches a one-hot sample as `tau -> 0`. The index of its maximum is
+    /// distributed as `p` at any temperature, and an index with a log weight of `-inf`
+    /// receives zero. The temperature must be positive and finite, and special values are
+    /// otherwise handled as in [`Softmax`]; where the result is undefined, it is `nan`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::GumbelSample;
+    /// use rand::{rngs::SmallRng, SeedableRng};
+    ///
+    /// let mut rng = SmallRng::seed_from_u64(1);
+    /// let ln_w: Vec<f64> = vec![0.0, f64::NEG_INFINITY, 1.0, 2.0];
+    /// let y = ln_w.gumbel_softmax(0.5, &mut rng);
+    /// assert!((y.iter().sum::<f64>() - 1.0).abs() < 1e-15);
+    /// assert_eq!(y[1], 0.0);
+    ///
+    /// assert!(ln_w.gumbel_softmax(0.0, &mut rng).iter().all(|y| y.is_nan()));
+    /// ```
+    fn gumbel_softmax<R: Rng + ?Sized>(&self, tau: Self::Output, rng: &mut R) -> Vec<Self::Output>;
+}
+
+macro_rules! impl_gumbel_sample {
+    { $($f:ident $mod:ident)+ } => {
+        $(
+            mod $mod {
+                use crate::math::ln;
+                use rand::distr::Open01;
+                use rand::Rng;
+
+                // A standard Gumbel variate, `-ln(-ln(u))` for `u` uniform on (0, 1).
+                pub(super) fn noise<R: Rng + ?Sized>(rng: &mut R) -> $f {
+                    let u: $f = rng.sample(Open01);
+                    -ln(-ln(u))
+                }
+            }
+
+            impl GumbelSample for [$f] {
+                type Output = $f;
+
+                fn gumbel_max<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+                    let mut argmax = None;
+                    let mut max = $f::NEG_INFINITY;
+                    // The indices of +inf are sampled uniformly, by reservoir sampling.
+                    let mut n_inf: usize = 0;
+                    for (i, x) in self.iter().enumerate() {
+                        if x.is_nan() {
+                            return None;
+                        } else if *x == $f::INFINITY {
+                            n_inf += 1;
+                            if rng.random_range(0..n_inf) == 0 {
+                                argmax = Some(i);
+                            }
+                        } else if n_inf == 0 && *x != $f::NEG_INFINITY {
+                            let key = x + $mod::noise(rng);
+                            if argmax.is_none() || key > max {
+                                argmax = Some(i);
+                                max = key;
+                            }
+                        }
+                    }
+                    argmax
+                }
+
+                fn gumbel_top_k<R: Rng + ?Sized>(&self, k: usize, rng: &mut R) -> Vec<usize> {
+                    // Keys are ordered first by whether the log weight is +inf, then by
+                    // the perturbed log weight, or, for +inf, the noise alone.
+                    let mut keys: Vec<(bool, $f, usize)> = Vec::new();
+                    for (i, x) in self.iter().enumerate() {
+                        if x.is_nan() {
+                            return Vec::new();
+                        } else if *x == $f::INFINITY {
+                            keys.push((true, $mod::noise(rng), i));
+                        } else if *x != $f::NEG_INFINITY {
+                            keys.push((false, x + $mod::noise(rng), i));
+                        }
+                    }
+                    let descending = |a: &(bool, $f, usize), b: &(bool, $f, usize)| {
+                        b.0.cmp(&a.0).then(b.1.total_cmp(&a.1))
+                    };
+                    let k = k.min(keys.len());
+                    if k == 0 {
+                        return Vec::new();
+                    }
+                    if k < keys.len() {
+                        keys.select_nth_unstable_by(k - 1, descending);
+                        keys.truncate(k);
+                    }
+                    keys.sort_unstable_by(descending);
+                    keys.into_iter().map(|(_, _, i)| i).collect()
+                }
+
+                fn gumbel_softmax<R: Rng + ?Sized>(&self, tau: $f, rng: &mut R) -> Vec<$f> {
+                    if !(tau.is_finite() && tau > 0.0) {
+                        return vec![$f::NAN; self.len()];
+                    }
+                    let mut y: Vec<$f> = self.iter().map(|x| (x + $mod::noise(rng)) / tau).collect();
+                    y.softmax_mut();
+                    y
+                }
+            }
+        )+
+    }
+}
+impl_gumbel_sample! { f64 f64_gumbel f32 f32_gumbel }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    macro_rules! gumbel_sample_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                const N: usize = 20_000;
+                // About 6 standard deviations of a frequency estimated from N draws.
+                const TOL: f64 = 0.02;
+
+                fn ln_w() -> Vec<$f> {
+                    vec![(1.0 as $f).ln(), $f::NEG_INFINITY, (2.0 as $f).ln(), (3.0 as $f).ln(), (4.0 as $f).ln()]
+                }
+
+                const P: [f64; 5] = [0.1, 0.0, 0.2, 0.3, 0.4];
+
+                fn assert_frequencies(counts: &[usize], p: &[f64]) {
+                    let n: usize = counts.iter().sum();
+                    for (c, p) in counts.iter().zip(p.iter()) {
+                        let freq = *c as f64 / n as f64;
+                        assert!((freq - p).abs() < TOL, "{:?} {:?}", counts, p);
+                    }
+                }
+
+                #[test]
+                fn gumbel_max_works() {
+                    let mut rng = SmallRng::seed_from_u64(0x5eed);
+                    let x = ln_w();
+                    let mut counts = [0; 5];
+                    for _ in 0..N {
+                        counts[x.gumbel_max(&mut rng).unwrap()] += 1;
+                    }
+                    assert_eq!(counts[1], 0);
+                    assert_frequencies(&counts, &P);
+
+                    // unnormalized, and shifted far from zero
+                    let x: Vec<$f> = x.iter().map(|x| x - 500.0).collect();
+                    let mut counts = [0; 5];
+                    for _ in 0..N {
+                        counts[x.gumbel_max(&mut rng).unwrap()] += 1;
+                    }
+                    assert_frequencies(&counts, &P);
+                }
+
+                #[test]
+                fn gumbel_max_special_values() {
+                    let mut rng = SmallRng::seed_from_u64(0x5eed);
+                    let (inf, neg_inf, nan) = ($f::INFINITY, $f::NEG_INFINITY, $f::NAN);
+                    let e: Vec<$f> = vec![];
+                    assert_eq!(e.gumbel_max(&mut rng), None);
+                    assert_eq!([neg_inf, neg_inf].gumbel_max(&mut rng), None);
+                    assert_eq!([1.0, nan, inf].gumbel_max(&mut rng), None);
+                    assert_eq!([neg_inf, 1.0].gumbel_max(&mut rng), Some(1));
+
+                    let x: Vec<$f> = vec![100.0, inf, neg_inf, inf, inf];
+                    let mut counts = [0; 5];
+                    for _ in 0..N {
+                        counts[x.gumbel_max(&mut rng).unwrap()] += 1;
+                    }
+                    assert_frequencies(&counts, &[0.0, 1.0 / 3.0, 0.0, 1.0 / 3.0, 1.0 / 3.0]);
+                }
+
+                #[test]
+                fn gumbel_top_k_works() {
+                    let mut rng = SmallRng::seed_from_u64(0x5eed);
+                    let x = ln_w();
+                    let mut first = [0; 5];
+                    let mut second = [0; 5];
+                    for _ in 0..N {
+                        let s = x.gumbel_top_k(2, &mut rng);
+                        assert_eq!(s.len(), 2);
+                        assert_ne!(s[0], s[1]);
+                        first[s[0]] += 1;
+                        second[s[1]] += 1;
+                    }
+                    assert_frequencies(&first, &P);
+                    // P(second = j) = sum_{i != j}(p_i * p_j / (1 - p_i))
+                    let q: Vec<f64> = (0..5)
+                        .map(|j| (0..5).filter(|i| *i != j).map(|i| P[i] * P[j] / (1.0 - P[i])).sum())
+                        .collect();
+                    assert_frequencies(&second, &q);
+
+                    // all indices of non-zero probability
+                    let mut s = x.gumbel_top_k(5, &mut rng);
+                    assert_eq!(s.len(), 4);
+                    s.sort_unstable();
+                    assert_eq!(s, vec![0, 2, 3, 4]);
+                    assert_eq!(x.gumbel_top_k(0, &mut rng), vec![]);
+
+                    let x = vec![(1.0 as $f), $f::NEG_INFINITY, $f::INFINITY, 2.0, $f::INFINITY];
+                    for _ in 0..100 {
+                        let mut s = x.gumbel_top_k(3, &mut rng);
+                        s[..2].sort_unstable();
+                        assert_eq!(&s[..2], &[2, 4]);
+                    }
+                    assert!([1.0, $f::NAN].gumbel_top_k(1, &mut rng).is_empty());
+                    assert!([$f::NEG_INFINITY; 3].gumbel_top_k(2, &mut rng).is_empty());
+                }
+
+                #[test]
+                fn gumbel_softmax_works() {
+                    let mut rng = SmallRng::seed_from_u64(0x5eed);
+                    let x = ln_w();
+                    let mut counts = [0; 5];
+                    for _ in 0..N {
+                        let y = x.gumbel_softmax(1.0, &mut rng);
+                        assert!((y.iter().sum::<$f>() - 1.0).abs() < 8.0 * $f::EPSILON);
+                        assert_eq!(y[1], 0.0);
+                        let argmax = (0..5).fold(0, |m, i| if y[i] > y[m] { i } else { m });
+                        counts[argmax] += 1;
+                    }
+                    assert_frequencies(&counts, &P);
+
+                    // nearly one-hot at low temperature
+                    let mut max = 0.0;
+                    for _ in 0..100 {
+                        let y = x.gumbel_softmax(1e-3, &mut rng);
+                        max += y.iter().copied().fold(0.0, $f::max) / 100.0;
+                    }
+                    assert!(max > 0.99);
+
+                    for tau in [0.0, -1.0, $f::INFINITY, $f::NAN] {
+                        assert!(x.gumbel_softmax(tau, &mut rng).iter().all(|y| y.is_nan()));
+                    }
+                    assert!([1.0, $f::NAN].gumbel_softmax(1.0, &mut rng).iter().all(|y| y.is_nan()));
+                    assert_eq!([1.0, $f::INFINITY].gumbel_softmax(1.0, &mut rng), vec![0.0, 1.0]);
+                }
+            }
+        }
+    }
+    gumbel_sample_tests! { f64_gumbel_sample_impl f64 }
+    gumbel_sample_tests! { f32_gumbel_sample_impl f32 }
+}
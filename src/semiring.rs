@@ -0,0 +1,307 @@
+use crate::{LogAddExp, LogSumExp};
+
+/// A [semiring](https://en.wikipedia.org/wiki/Semiring) over values of type `T`, i.e. a
+/// pair of associative operations, `add` and `mul`, with identities `zero` and `one`,
+/// where `mul` distributes over `add` and `zero` annihilates under `mul`. Dynamic
+/// programming recurrences, e.g. the forward algorithm and the Viterbi algorithm, differ
+/// only in the semiring, hence, may be written once, generic over `S: Semiring<T>`.
+///
+/// The semirings are zero-sized types, which are not instantiated; the operations are
+/// associated functions. The following are provided for `f64` and `f32`:
+///
+/// | Semiring         | `add`         | `mul` | `zero` | `one` |
+/// |------------------|---------------|-------|--------|-------|
+/// | [`LogSemiring`]  | `ln_add_exp`  | `+`   | `-inf` | `0`   |
+/// | [`MaxPlus`]      | `max`         | `+`   | `-inf` | `0`   |
+/// | [`MinPlus`]      | `min`         | `+`   | `inf`  | `0`   |
+/// | [`Real`]         | `+`           | `*`   | `0`    | `1`   |
+///
+/// In contrast to `f64::max` and `f64::min`, `add` of `MaxPlus` and `MinPlus` propagates
+/// `nan`, consistently with `LogSumExp`.
+///
+/// # Examples
+/// ```
+/// use logsumexp::{LogSemiring, LogSumExp, MaxPlus, Semiring};
+///
+/// // The score of a two-step path, summed over all intermediate states `j`:
+/// // log-likelihood (forward) or best path (Viterbi), depending on the semiring.
+/// fn two_step<S: Semiring<f64>>(a: &[f64], b: &[f64]) -> f64 {
+///     S::sum(a.iter().zip(b.iter()).map(|(x, y)| S::mul(*x, *y)))
+/// }
+///
+/// let a: Vec<f64> = vec![0.5, -1.0, 0.25];
+/// let b: Vec<f64> = vec![-0.5, 2.0, 0.0];
+/// let forward = two_step::<LogSemiring>(&a, &b);
+/// assert_eq!(forward, [0.0, 1.0, 0.25].iter().ln_sum_exp());
+/// let viterbi = two_step::<MaxPlus>(&a, &b);
+/// assert_eq!(viterbi, 1.0);
+/// ```
+pub trait Semiring<T: Copy> {
+    /// Return the additive identity, which is also the annihilator of `mul`.
+    fn zero() -> T;
+
+    /// Return the multiplicative identity.
+    fn one() -> T;
+
+    /// Return the semiring sum of `a` and `b`.
+    fn add(a: T, b: T) -> T;
+
+    /// Return the semiring product of `a` and `b`.
+    fn mul(a: T, b: T) -> T;
+
+    /// Return the semiring sum of the sequence, which is `zero` if it is empty.
+    fn sum<I: IntoIterator<Item = T>>(iter: I) -> T {
+        iter.into_iter().fold(Self::zero(), Self::add)
+    }
+
+    /// Return the semiring product of the sequence, which is `one` if it is empty.
+    fn product<I: IntoIterator<Item = T>>(iter: I) -> T {
+        iter.into_iter().fold(Self::one(), Self::mul)
+    }
+}
+
+/// The log semiring, in which `add` is [`LogAddExp`] and `mul` is `+`, i.e. the image of
+/// the non-negative reals under `ln`. The sum of a sequence is evaluated by the 1-pass
+/// algorithm of [`LogSumExp`], rather than by repeated `ln_add_exp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LogSemiring;
+
+/// The max-plus (tropical) semiring, in which `add` is `max` and `mul` is `+`; the
+/// semiring of the Viterbi algorithm in log space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MaxPlus;
+
+/// The min-plus (tropical) semiring, in which `add` is `min` and `mul` is `+`; the
+/// semiring of shortest paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MinPlus;
+
+/// The semiring of ordinary real arithmetic, in which `add` is `+` and `mul` is `*`,
+/// e.g. for probabilities on the linear scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Real;
+
+/// A trait for reducing a sequence by the operations of a [`Semiring`], selected by a
+/// type parameter, e.g. `v.iter().semiring_sum::<MaxPlus>()`.
+pub trait SemiringReduce<T, U: Iterator<Item = T>> {
+    type Output: Copy;
+
+    /// Return the semiring sum of the sequence, which is `S::zero()` if it is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSemiring, LogSumExp, MaxPlus, MinPlus, Real, SemiringReduce};
+    ///
+    /// let v: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// assert_eq!(v.iter().semiring_sum::<LogSemiring>(), v.iter().ln_sum_exp());
+    /// assert_eq!(v.iter().semiring_sum::<MaxPlus>(), 2.0);
+    /// assert_eq!(v.iter().semiring_sum::<MinPlus>(), -1.0);
+    /// assert_eq!(v.into_iter().semiring_sum::<Real>(), 1.5);
+    /// ```
+    fn semiring_sum<S: Semiring<Self::Output>>(self) -> Self::Output;
+
+    /// Return the semiring product of the sequence, which is `S::one()` if it is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSemiring, Real, SemiringReduce};
+    ///
+    /// let v: Vec<f64> = vec![0.5, -1.0, 2.0];
+    /// assert_eq!(v.iter().semiring_product::<LogSemiring>(), 1.5);
+    /// assert_eq!(v.iter().semiring_product::<Real>(), -1.0);
+    /// ```
+    fn semiring_product<S: Semiring<Self::Output>>(self) -> Self::Output;
+}
+
+macro_rules! impl_semiring {
+    { $($f:ident)+ } => {
+        $(
+            impl Semiring<$f> for LogSemiring {
+                fn zero() -> $f {
+                    $f::NEG_INFINITY
+                }
+                fn one() -> $f {
+                    0.0
+                }
+                fn add(a: $f, b: $f) -> $f {
+                    a.ln_add_exp(b)
+                }
+                fn mul(a: $f, b: $f) -> $f {
+                    a + b
+                }
+                fn sum<I: IntoIterator<Item = $f>>(iter: I) -> $f {
+                    iter.into_iter().ln_sum_exp()
+                }
+            }
+
+            impl Semiring<$f> for MaxPlus {
+                fn zero() -> $f {
+                    $f::NEG_INFINITY
+                }
+                fn one() -> $f {
+                    0.0
+                }
+                fn add(a: $f, b: $f) -> $f {
+                    if a.is_nan() || a >= b {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                fn mul(a: $f, b: $f) -> $f {
+                    a + b
+                }
+            }
+
+            impl Semiring<$f> for MinPlus {
+                fn zero() -> $f {
+                    $f::INFINITY
+                }
+                fn one() -> $f {
+                    0.0
+                }
+                fn add(a: $f, b: $f) -> $f {
+                    if a.is_nan() || a <= b {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                fn mul(a: $f, b: $f) -> $f {
+                    a + b
+                }
+            }
+
+            impl Semiring<$f> for Real {
+                fn zero() -> $f {
+                    0.0
+                }
+                fn one() -> $f {
+                    1.0
+                }
+                fn add(a: $f, b: $f) -> $f {
+                    a + b
+                }
+                fn mul(a: $f, b: $f) -> $f {
+                    a * b
+                }
+            }
+
+            impl<U> SemiringReduce<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = $f;
+                fn semiring_sum<S: Semiring<$f>>(self) -> Self::Output {
+                    S::sum(self)
+                }
+                fn semiring_product<S: Semiring<$f>>(self) -> Self::Output {
+                    S::product(self)
+                }
+            }
+
+            impl<'a, U> SemiringReduce<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = $f;
+                fn semiring_sum<S: Semiring<$f>>(self) -> Self::Output {
+                    S::sum(self.copied())
+                }
+                fn semiring_product<S: Semiring<$f>>(self) -> Self::Output {
+                    S::product(self.copied())
+                }
+            }
+        )+
+    }
+}
+impl_semiring! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! semiring_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn same(a: $f, b: $f) -> bool {
+                    a == b || (a.is_nan() && b.is_nan())
+                }
+
+                // The semiring axioms, checked on a few values; exact for values whose
+                // sums are exact.
+                fn axioms<S: Semiring<$f>>(values: &[$f]) {
+                    for &a in values {
+                        assert!(same(S::add(a, S::zero()), a));
+                        assert!(same(S::add(S::zero(), a), a));
+                        assert!(same(S::mul(a, S::one()), a));
+                        assert!(same(S::mul(S::one(), a), a));
+                        assert!(same(S::mul(a, S::zero()), S::zero()));
+                        for &b in values {
+                            assert!(same(S::add(a, b), S::add(b, a)));
+                            assert!(same(S::mul(a, b), S::mul(b, a)));
+                            for &c in values {
+                                let lhs = S::mul(a, S::add(b, c));
+                                let rhs = S::add(S::mul(a, b), S::mul(a, c));
+                                assert!((lhs - rhs).abs() <= 4.0 * $f::EPSILON * lhs.abs().max(1.0));
+                            }
+                        }
+                    }
+                }
+
+                #[test]
+                fn axioms_hold() {
+                    let v: Vec<$f> = vec![-1.5, 0.0, 0.5, 2.0];
+                    axioms::<LogSemiring>(&v);
+                    axioms::<MaxPlus>(&v);
+                    axioms::<MinPlus>(&v);
+                    axioms::<Real>(&v);
+                }
+
+                #[test]
+                fn reduce_works() {
+                    let v: Vec<$f> = vec![0.5, -1.0, 2.0, $f::NEG_INFINITY];
+
+                    assert_eq!(v.iter().semiring_sum::<LogSemiring>(), v.iter().ln_sum_exp());
+                    assert_eq!(v.iter().semiring_sum::<MaxPlus>(), 2.0);
+                    assert_eq!(v.iter().semiring_sum::<MinPlus>(), $f::NEG_INFINITY);
+                    assert_eq!(v[..3].iter().semiring_sum::<Real>(), 1.5);
+                    assert_eq!(v[..3].iter().semiring_product::<LogSemiring>(), 1.5);
+                    assert_eq!(v[..3].iter().semiring_product::<MaxPlus>(), 1.5);
+                    assert_eq!(v[..3].iter().semiring_product::<MinPlus>(), 1.5);
+                    assert_eq!(v[..3].iter().semiring_product::<Real>(), -1.0);
+                    assert_eq!(
+                        v.clone().into_iter().semiring_sum::<LogSemiring>(),
+                        v.iter().semiring_sum::<LogSemiring>()
+                    );
+
+                    let v: Vec<$f> = vec![];
+                    assert_eq!(v.iter().semiring_sum::<LogSemiring>(), $f::NEG_INFINITY);
+                    assert_eq!(v.iter().semiring_sum::<MaxPlus>(), $f::NEG_INFINITY);
+                    assert_eq!(v.iter().semiring_sum::<MinPlus>(), $f::INFINITY);
+                    assert_eq!(v.iter().semiring_sum::<Real>(), 0.0);
+                    assert_eq!(v.iter().semiring_product::<LogSemiring>(), 0.0);
+                    assert_eq!(v.iter().semiring_product::<Real>(), 1.0);
+                }
+
+                #[test]
+                fn nan_propagates() {
+                    let nan = $f::NAN;
+                    for v in [vec![nan, 1.0, 2.0], vec![1.0, nan, 2.0], vec![1.0, 2.0, nan]] {
+                        assert!(v.iter().semiring_sum::<LogSemiring>().is_nan());
+                        assert!(v.iter().semiring_sum::<MaxPlus>().is_nan());
+                        assert!(v.iter().semiring_sum::<MinPlus>().is_nan());
+                        assert!(v.iter().semiring_sum::<Real>().is_nan());
+                    }
+                }
+            }
+        }
+    }
+    semiring_tests! { f64_semiring_impl f64 }
+    semiring_tests! { f32_semiring_impl f32 }
+}
@@ -0,0 +1,270 @@
+use crate::math::ln;
+#[cfg(not(feature = "std"))]
+use crate::math::LnExp;
+use crate::OnlineLogSumExp;
+use core::fmt;
+#[cfg(feature = "std")]
+use lnexp::LnExp;
+
+/// The error returned by [`SignedLogSumExp`] when the positive and negative terms of
+/// the sum cancel to the extent that the result carries no significant digits, i.e.
+/// when the magnitude of the result is less than `EPSILON` times the larger of the
+/// magnitudes of the positive and negative parts, or when both parts are `+inf`, in which
+/// case the sum is undefined. (Exact cancellation of finite parts is not an error: the
+/// sum is zero.)
+///
+/// The log of the sums of the positive and negative parts are provided, so that the
+/// caller may decide how to proceed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CancellationError<T> {
+    /// The log of the sum of the terms with positive coefficients.
+    pub ln_positive: T,
+    /// The log of the sum of the terms with negative coefficients, in absolute value.
+    pub ln_negative: T,
+}
+
+impl<T: fmt::Display> fmt::Display for CancellationError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "catastrophic cancellation in signed log-sum-exp: ln(positive) = {}, ln(negative) = {}",
+            self.ln_positive, self.ln_negative
+        )
+    }
+}
+
+impl<T: fmt::Debug + fmt::Display> core::error::Error for CancellationError<T> {}
+
+/// A trait for computing the log of the absolute value of the sum of exponentials
+/// with real (possibly negative) coefficients, `ln|s_1 * exp(x_1) + s_2 * exp(x_2) + ...|`,
+/// together with the sign of the sum. The sequence consists of `(value, coefficient)`
+/// pairs.
+///
+/// The terms with positive and negative coefficients are each accumulated using the
+/// 1-pass (online) algorithm of [`WeightedLogSumExp`](crate::WeightedLogSumExp), then
+/// combined using `ln_1m_exp`. Terms with zero coefficient are excluded, irrespective
+/// of their value. If the positive and negative parts are equal and finite, the sum is
+/// zero, with sign `0` and log-magnitude `-inf`; note that the parts are compared after
+/// rounding, hence, terms smaller than the rounding error of either part do not prevent
+/// this. If a `nan` is encountered, the
+/// sign and log-magnitude are `nan`.
+pub trait SignedLogSumExp<T, U: Iterator<Item = (T, T)>> {
+    type Output;
+
+    /// Return the sign (`1`, `-1` or `0`) and the log of the absolute value of the
+    /// sum, or a [`CancellationError`] if the result has no significant digits.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SignedLogSumExp;
+    ///
+    /// // exp(1) - exp(0) - 0.5 * exp(0)
+    /// let v: Vec<(f64, f64)> = vec![(1.0, 1.0), (0.0, -1.0), (0.0, -0.5)];
+    /// let (sign, ln_abs) = v.into_iter().ln_sum_exp_signed().unwrap();
+    /// assert_eq!(sign, 1.0);
+    /// assert!((ln_abs - (1.0_f64.exp() - 1.5).ln()).abs() < 2.0 * f64::EPSILON);
+    ///
+    /// // alternating series: 1 - 2 + 3 - 4 = -2
+    /// let x: Vec<f64> = vec![1.0_f64.ln(), 2.0_f64.ln(), 3.0_f64.ln(), 4.0_f64.ln()];
+    /// let s: Vec<f64> = vec![1.0, -1.0, 1.0, -1.0];
+    /// let (sign, ln_abs) = x.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+    /// assert_eq!(sign, -1.0);
+    /// assert!((ln_abs - 2.0_f64.ln()).abs() < 4.0 * f64::EPSILON);
+    ///
+    /// // the empty sum is exactly zero
+    /// let v: Vec<(f64, f64)> = vec![];
+    /// assert_eq!(v.into_iter().ln_sum_exp_signed(), Ok((0.0, f64::NEG_INFINITY)));
+    ///
+    /// // exact cancellation is zero
+    /// let v: Vec<(f64, f64)> = vec![(1.0, 1.0), (1.0, -1.0)];
+    /// assert_eq!(v.into_iter().ln_sum_exp_signed(), Ok((0.0, f64::NEG_INFINITY)));
+    ///
+    /// // catastrophic cancellation is reported
+    /// let v: Vec<(f64, f64)> = vec![(0.0, 1.0), (0.0, -(1.0 - f64::EPSILON / 2.0))];
+    /// assert!(v.into_iter().ln_sum_exp_signed().is_err());
+    /// ```
+    fn ln_sum_exp_signed(self) -> Self::Output;
+}
+
+macro_rules! impl_signed_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> SignedLogSumExp<$f, U> for U
+            where
+                U: Iterator<Item = ($f, $f)>,
+            {
+                type Output = Result<($f, $f), CancellationError<$f>>;
+                fn ln_sum_exp_signed(self) -> Self::Output {
+                    let mut pos = OnlineLogSumExp::<$f>::new();
+                    let mut neg = OnlineLogSumExp::<$f>::new();
+                    for (x, s) in self {
+                        if s < 0.0 {
+                            neg.push_weighted(x, -s);
+                        } else {
+                            pos.push_weighted(x, s);
+                        }
+                    }
+                    let (p, n) = (pos.finish(), neg.finish());
+                    if p.is_nan() {
+                        return Ok((p, p));
+                    } else if n.is_nan() {
+                        return Ok((n, n));
+                    }
+                    let (sign, max, min) = if p > n {
+                        (1.0, p, n)
+                    } else if p < n {
+                        (-1.0, n, p)
+                    } else if p == $f::INFINITY {
+                        // inf - inf is undefined
+                        return Err(CancellationError { ln_positive: p, ln_negative: n });
+                    } else {
+                        // the parts cancel exactly, or both are empty
+                        return Ok((0.0, $f::NEG_INFINITY));
+                    };
+                    let ln_abs = max + (min - max).ln_1m_exp();
+                    if max.is_finite() && max - ln_abs > -ln($f::EPSILON) {
+                        Err(CancellationError { ln_positive: p, ln_negative: n })
+                    } else {
+                        Ok((sign, ln_abs))
+                    }
+                }
+            }
+
+            impl<'a, U> SignedLogSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = (&'a $f, &'a $f)>,
+            {
+                type Output = Result<($f, $f), CancellationError<$f>>;
+                fn ln_sum_exp_signed(self) -> Self::Output {
+                    self.map(|(x, s)| (*x, *s)).ln_sum_exp_signed()
+                }
+            }
+        )+
+    }
+}
+impl_signed_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! signed_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn ln_sum_exp_signed_works() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    // all coefficients positive
+                    let v = vec![neg_inf, x, y];
+                    let s: Vec<$f> = vec![1.0; 3];
+                    let (sign, ln_abs) = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+                    assert_eq!(sign, 1.0);
+                    assert_eq!(ln_abs, v.iter().ln_sum_exp());
+
+                    // all coefficients negative
+                    let s: Vec<$f> = vec![-1.0; 3];
+                    let (sign, ln_abs) = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+                    assert_eq!(sign, -1.0);
+                    assert_eq!(ln_abs, v.iter().ln_sum_exp());
+
+                    // mixed
+                    let v = vec![x, y];
+                    let s: Vec<$f> = vec![3.0, -1.0];
+                    let rhs: $f = (3.0 * x.exp() - y.exp()).ln();
+                    let (sign, ln_abs) = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+                    assert_eq!(sign, 1.0);
+                    assert!((ln_abs - rhs).abs() < 4.0 * $f::EPSILON);
+
+                    let s: Vec<$f> = vec![-3.0, 1.0];
+                    let (sign, ln_abs) = v.into_iter().zip(s.into_iter()).ln_sum_exp_signed().unwrap();
+                    assert_eq!(sign, -1.0);
+                    assert!((ln_abs - rhs).abs() < 4.0 * $f::EPSILON);
+
+                    // zero coefficients
+                    let v = vec![x, inf, $f::NAN];
+                    let s: Vec<$f> = vec![-1.0, 0.0, -0.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((-1.0, x)));
+
+                    // +/-inf
+                    let v = vec![x, inf];
+                    let s: Vec<$f> = vec![1.0, -1.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((-1.0, inf)));
+
+                    let v = vec![inf, x, neg_inf];
+                    let s: Vec<$f> = vec![1.0, -1.0, -1.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((1.0, inf)));
+
+                    let v = vec![inf, inf];
+                    let s: Vec<$f> = vec![1.0, -1.0];
+                    let err = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap_err();
+                    assert_eq!(err, CancellationError { ln_positive: inf, ln_negative: inf });
+
+                    // Edge cases
+                    let v: Vec<($f, $f)> = vec![];
+                    assert_eq!(v.into_iter().ln_sum_exp_signed(), Ok((0.0, neg_inf)));
+
+                    let v = vec![neg_inf, neg_inf];
+                    let s: Vec<$f> = vec![1.0, -1.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((0.0, neg_inf)));
+                }
+
+                #[test]
+                fn ln_sum_exp_signed_nan() {
+                    let nan: $f = $f::NAN;
+                    let inf: $f = $f::INFINITY;
+                    let x: $f = 0.5;
+
+                    let cases: Vec<(Vec<$f>, Vec<$f>)> = vec![
+                        (vec![x, nan], vec![1.0, -1.0]),
+                        (vec![nan, x], vec![1.0, -1.0]),
+                        (vec![x, x], vec![1.0, nan]),
+                        (vec![inf, nan], vec![1.0, -1.0]),
+                        (vec![inf, x], vec![-1.0, nan]),
+                    ];
+                    for (v, s) in cases {
+                        let (sign, ln_abs) = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+                        assert!(sign.is_nan() && ln_abs.is_nan());
+                    }
+                }
+
+                #[test]
+                fn ln_sum_exp_signed_cancellation() {
+                    let x: $f = 0.5;
+
+                    // exact cancellation is zero
+                    let v = vec![x, x];
+                    let s: Vec<$f> = vec![1.0, -1.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((0.0, $f::NEG_INFINITY)));
+                    let v = vec![x, x, x + (2.0 as $f).ln()];
+                    let s: Vec<$f> = vec![1.0, 1.0, -1.0];
+                    assert_eq!(v.iter().zip(s.iter()).ln_sum_exp_signed(), Ok((0.0, $f::NEG_INFINITY)));
+
+                    // the parts agree in all but the last bit
+                    let v: Vec<$f> = vec![0.0, 0.0];
+                    let s: Vec<$f> = vec![1.0, -(1.0 - $f::EPSILON / 2.0)];
+                    assert!(v.iter().zip(s.iter()).ln_sum_exp_signed().is_err());
+
+                    // severe, but not complete, loss of precision is permitted
+                    let v = vec![1.0, 1.0];
+                    let s: Vec<$f> = vec![1.0, -(1.0 - 1024.0 * $f::EPSILON)];
+                    let (sign, ln_abs) = v.iter().zip(s.iter()).ln_sum_exp_signed().unwrap();
+                    assert_eq!(sign, 1.0);
+                    let rhs = 1.0 + (1024.0 * $f::EPSILON).ln();
+                    assert!((ln_abs - rhs).abs() < 1e-3);
+                }
+            }
+        }
+    }
+    signed_logsumexp_tests! { f64_signed_logsumexp_impl f64 }
+    signed_logsumexp_tests! { f32_signed_logsumexp_impl f32 }
+}
@@ -0,0 +1,354 @@
+use crate::math;
+use crate::OnlineLogSumExp;
+
+/// A trait for computing the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of a
+/// contiguous slice using a vectorized kernel.
+///
+/// The slice is processed in blocks, each of which is distributed over a fixed number of
+/// lanes. Each lane holds the state of the online algorithm (running maximum and scaled
+/// sum); the state is rescaled once per block, using the maximum of the block, after
+/// which the exponentials of the block are accumulated using a branch-free polynomial
+/// approximation of `exp`. On `x86_64`, the kernel is compiled for AVX-512, AVX2 and
+/// SSE2, one of which is selected at runtime based on the features supported by the CPU;
+/// on other architectures, the kernel is compiled for the target's baseline features.
+///
+/// Special values are handled identically to [`LogSumExp`](crate::LogSumExp): `-inf` is handled by the
+/// vectorized kernel, whereas a slice which contains `+inf` or `nan` is detected at the
+/// end of the vectorized pass, and re-evaluated by the iterator implementation.
+///
+/// The result differs from that of `LogSumExp` only due to the order of summation and
+/// the approximation of `exp`, the latter of which has a relative error of at most
+/// `2 * EPSILON` with respect to the `exp` of the standard library over the range in
+/// which it is applied. For slices of up to `10^4` elements, the
+/// result agrees with that of `LogSumExp` to within [`SLICE_ULP_BOUND`] multiples of
+/// `EPSILON * max(1, |x|)`, where `x` is the result of `LogSumExp`.
+pub trait SliceLogSumExp {
+    type Output;
+
+    /// Return the [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp) of the slice.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, SliceLogSumExp, SLICE_ULP_BOUND};
+    ///
+    /// let v: Vec<f64> = (1..1000).map(|x| (x as f64).ln()).collect();
+    /// let rhs = v.iter().ln_sum_exp();
+    /// let tol = SLICE_ULP_BOUND * f64::EPSILON * rhs.abs().max(1.0);
+    /// assert!((v.ln_sum_exp_slice() - rhs).abs() <= tol);
+    ///
+    /// // special values
+    /// let v: Vec<f64> = vec![f64::NEG_INFINITY, 0.5, f64::NEG_INFINITY];
+    /// assert_eq!(v.ln_sum_exp_slice(), 0.5);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5, f64::NEG_INFINITY];
+    /// assert_eq!(v.ln_sum_exp_slice(), f64::INFINITY);
+    ///
+    /// let v: Vec<f64> = vec![f64::INFINITY, 0.5, f64::NAN];
+    /// assert!(v.ln_sum_exp_slice().is_nan());
+    /// ```
+    fn ln_sum_exp_slice(&self) -> Self::Output;
+}
+
+/// The bound, in multiples of `EPSILON * max(1, |x|)`, on the difference between the
+/// result of [`SliceLogSumExp`] and that of [`LogSumExp`](crate::LogSumExp) for slices of up to `10^4`
+/// elements.
+pub const SLICE_ULP_BOUND: f64 = 8.0;
+
+// The state of the online algorithm for a slice, as accumulated by the vectorized kernel,
+// so that the states of chunks may be merged without rounding each to a LogSumExp.
+pub(crate) trait SliceLogSumExpState {
+    type Output;
+
+    fn ln_sum_exp_state(&self) -> OnlineLogSumExp<Self::Output>;
+}
+
+macro_rules! impl_slice_logsumexp {
+    { $f:ident, $u:ident, $modname:ident, $lanes:expr, $bias:expr, $low:expr,
+      $ln2_hi:expr, $ln2_lo:expr, [$($c:expr),+] } => {
+        mod $modname {
+            use super::*;
+
+            const LANES: usize = $lanes;
+            // The number of elements between rescaling of the lane states.
+            const BLOCK: usize = 4 * LANES;
+            // exp(x) is flushed to zero below this point, which is chosen such that
+            // the result of the polynomial approximation is a normal number.
+            pub(super) const LOW: $f = $low;
+            // 1.5 * 2^(mantissa digits - 1); adding this rounds to the nearest integer,
+            // which may then be read from the low bits of the sum.
+            const SHIFT: $f = 1.5 * ((1 as $u) << ($f::MANTISSA_DIGITS - 1)) as $f;
+            const LN2_HI: $f = $ln2_hi;
+            const LN2_LO: $f = $ln2_lo;
+
+            // Branch-free exp(x) for x <= 0, `-inf` or `nan`; Cody-Waite range reduction,
+            // followed by a Taylor polynomial on |r| <= ln(2)/2.
+            #[inline(always)]
+            fn exp(x: $f) -> $f {
+                let xc = if x < LOW { LOW } else { x };
+                let t = xc * core::$f::consts::LOG2_E + SHIFT;
+                let k = t - SHIFT;
+                let r = xc - k * LN2_HI - k * LN2_LO;
+                let mut p: $f = 0.0;
+                $( p = p * r + $c; )+
+                let scale = $f::from_bits(
+                    t.to_bits()
+                        .wrapping_sub(SHIFT.to_bits())
+                        .wrapping_add($bias)
+                        << ($f::MANTISSA_DIGITS - 1),
+                );
+                let y = p * scale;
+                if x < LOW {
+                    0.0
+                } else {
+                    y
+                }
+            }
+
+            // Update the state of a single lane with a single value.
+            #[inline(always)]
+            fn update(m: &mut $f, s: &mut $f, x: $f) {
+                let m_new = m.max(x);
+                *s = *s * exp(*m - m_new) + exp(x - m_new);
+                *m = m_new;
+            }
+
+            // Lanes which have yet to encounter a finite value hold a maximum of `MIN`,
+            // rather than `-inf`, so that `-inf` values do not produce `-inf - -inf`.
+            #[inline(always)]
+            pub(super) fn kernel(v: &[$f]) -> OnlineLogSumExp<$f> {
+                let mut m = [$f::MIN; LANES];
+                let mut s: [$f; LANES] = [0.0; LANES];
+                let mut blocks = v.chunks_exact(BLOCK);
+                for block in &mut blocks {
+                    let mut m_new = m;
+                    for row in block.chunks_exact(LANES) {
+                        for j in 0..LANES {
+                            m_new[j] = m_new[j].max(row[j]);
+                        }
+                    }
+                    for j in 0..LANES {
+                        s[j] *= exp(m[j] - m_new[j]);
+                    }
+                    m = m_new;
+                    for row in block.chunks_exact(LANES) {
+                        for j in 0..LANES {
+                            s[j] += exp(row[j] - m[j]);
+                        }
+                    }
+                }
+                for (j, x) in blocks.remainder().iter().enumerate() {
+                    update(&mut m[j % LANES], &mut s[j % LANES], *x);
+                }
+                // Combine the lanes; `nan` indicates that `+inf` or `nan` was encountered.
+                let max = m.iter().fold($f::MIN, |acc, m_j| acc.max(*m_j));
+                let sum: $f = m.iter().zip(s.iter()).map(|(m_j, s_j)| s_j * math::exp(m_j - max)).sum();
+                if sum.is_nan() {
+                    v.iter().collect()
+                } else if sum == 0.0 {
+                    OnlineLogSumExp::<$f>::new()
+                } else {
+                    OnlineLogSumExp::<$f>::from_parts(max, sum)
+                }
+            }
+
+            #[cfg(all(feature = "std", target_arch = "x86_64"))]
+            #[target_feature(enable = "avx512f")]
+            pub(super) fn kernel_avx512(v: &[$f]) -> OnlineLogSumExp<$f> {
+                kernel(v)
+            }
+
+            #[cfg(all(feature = "std", target_arch = "x86_64"))]
+            #[target_feature(enable = "avx2,fma")]
+            pub(super) fn kernel_avx2(v: &[$f]) -> OnlineLogSumExp<$f> {
+                kernel(v)
+            }
+
+            #[cfg(test)]
+            pub(super) fn exp_approx(x: $f) -> $f {
+                exp(x)
+            }
+        }
+
+        impl SliceLogSumExp for [$f] {
+            type Output = $f;
+            fn ln_sum_exp_slice(&self) -> Self::Output {
+                self.ln_sum_exp_state().finish()
+            }
+        }
+
+        impl SliceLogSumExpState for [$f] {
+            type Output = $f;
+            fn ln_sum_exp_state(&self) -> OnlineLogSumExp<$f> {
+                // Runtime detection of CPU features requires `std`.
+                #[cfg(all(feature = "std", target_arch = "x86_64"))]
+                {
+                    if is_x86_feature_detected!("avx512f") {
+                        // SAFETY: the CPU supports the features enabled for the kernel.
+                        return unsafe { $modname::kernel_avx512(self) };
+                    } else if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                        // SAFETY: the CPU supports the features enabled for the kernel.
+                        return unsafe { $modname::kernel_avx2(self) };
+                    }
+                }
+                // SSE2 is part of the x86_64 baseline.
+                $modname::kernel(self)
+            }
+        }
+    }
+}
+
+impl_slice_logsumexp! {
+    f64, u64, f64_kernel, 8, 1023, -708.0,
+    0.6931471803691238, 1.9082149292705877e-10,
+    [
+        1.0 / 6227020800.0,
+        1.0 / 479001600.0,
+        1.0 / 39916800.0,
+        1.0 / 3628800.0,
+        1.0 / 362880.0,
+        1.0 / 40320.0,
+        1.0 / 5040.0,
+        1.0 / 720.0,
+        1.0 / 120.0,
+        1.0 / 24.0,
+        1.0 / 6.0,
+        1.0 / 2.0,
+        1.0,
+        1.0
+    ]
+}
+impl_slice_logsumexp! {
+    f32, u32, f32_kernel, 16, 127, -87.0,
+    0.6933594, -2.1219444e-4,
+    [
+        1.0 / 5040.0,
+        1.0 / 720.0,
+        1.0 / 120.0,
+        1.0 / 24.0,
+        1.0 / 6.0,
+        1.0 / 2.0,
+        1.0,
+        1.0
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! slice_logsumexp_tests {
+        { $name:ident $f:ident $modname:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn assert_within_bound(v: &[$f]) {
+                    let lhs = v.ln_sum_exp_slice();
+                    let rhs = v.iter().ln_sum_exp();
+                    if rhs.is_nan() {
+                        assert!(lhs.is_nan());
+                    } else if !rhs.is_finite() {
+                        assert_eq!(lhs, rhs);
+                    } else {
+                        let tol = SLICE_ULP_BOUND as $f * $f::EPSILON * rhs.abs().max(1.0);
+                        assert!((lhs - rhs).abs() <= tol, "n={} {} {}", v.len(), lhs, rhs);
+                    }
+                }
+
+                #[test]
+                fn exp_approx_works() {
+                    for x in values::<$f>(100_000, $modname::LOW, 0.0) {
+                        let lhs = $modname::exp_approx(x);
+                        let rhs = x.exp();
+                        assert!((lhs - rhs).abs() <= 2.0 * $f::EPSILON * rhs, "{} {} {}", x, lhs, rhs);
+                    }
+                    assert_eq!($modname::exp_approx(0.0), 1.0);
+                    assert_eq!($modname::exp_approx($f::NEG_INFINITY), 0.0);
+                    assert_eq!($modname::exp_approx($f::MIN), 0.0);
+                    assert!($modname::exp_approx($f::NAN).is_nan());
+                }
+
+                #[test]
+                fn ln_sum_exp_slice_works() {
+                    for n in 0..=130 {
+                        assert_within_bound(&values::<$f>(n, -10.0, 10.0));
+                    }
+                    for &(lo, hi) in [(-1.0, 1.0), (-50.0, 50.0), (-1000.0, -900.0), (30.0, 80.0)].iter() {
+                        assert_within_bound(&values::<$f>(10_000, lo, hi));
+                    }
+                    // all of one value
+                    assert_within_bound(&vec![0.5; 1000]);
+                    // dynamic range of the sequence exceeds that of exp
+                    let mut v = values::<$f>(1000, -10.0, 10.0);
+                    v[500] = 1000.0;
+                    v[999] = -1000.0;
+                    assert_within_bound(&v);
+                }
+
+                #[test]
+                fn kernels_agree() {
+                    for n in [0, 1, 63, 64, 65, 1000] {
+                        let mut v = values::<$f>(n, -10.0, 10.0);
+                        let rhs = $modname::kernel(&v).finish();
+                        #[cfg(all(feature = "std", target_arch = "x86_64"))]
+                        {
+                            if is_x86_feature_detected!("avx512f") {
+                                assert_eq!(unsafe { $modname::kernel_avx512(&v) }.finish(), rhs);
+                            }
+                            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                                assert_eq!(unsafe { $modname::kernel_avx2(&v) }.finish(), rhs);
+                            }
+                        }
+                        assert_eq!(v.ln_sum_exp_slice(), rhs);
+                        if n > 0 {
+                            v[n / 2] = $f::NEG_INFINITY;
+                            assert_eq!(v.ln_sum_exp_slice(), $modname::kernel(&v).finish());
+                        }
+                    }
+                }
+
+                #[test]
+                fn ln_sum_exp_slice_special_values() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+
+                    let v: Vec<$f> = vec![];
+                    assert_eq!(v.ln_sum_exp_slice(), neg_inf);
+                    for n in [1, 7, 64, 65, 1000] {
+                        assert_eq!(vec![neg_inf; n].ln_sum_exp_slice(), neg_inf);
+                        assert_eq!(vec![inf; n].ln_sum_exp_slice(), inf);
+                        assert!(vec![nan; n].ln_sum_exp_slice().is_nan());
+                    }
+
+                    let v = values::<$f>(1000, -10.0, 10.0);
+                    for &special in [inf, neg_inf, nan].iter() {
+                        for &i in [0, 1, 63, 64, 500, 998, 999].iter() {
+                            let mut w = v.clone();
+                            w[i] = special;
+                            assert_within_bound(&w);
+                        }
+                    }
+
+                    // a single finite value amongst many -inf
+                    for &i in [0, 31, 32, 500, 999].iter() {
+                        let mut w = vec![neg_inf; 1000];
+                        w[i] = 0.5;
+                        assert_eq!(w.ln_sum_exp_slice(), 0.5);
+                    }
+
+                    let mut w = v.clone();
+                    w[10] = inf;
+                    w[900] = nan;
+                    assert!(w.ln_sum_exp_slice().is_nan());
+                }
+            }
+        }
+    }
+    slice_logsumexp_tests! { f64_slice_logsumexp_impl f64 f64_kernel }
+    slice_logsumexp_tests! { f32_slice_logsumexp_impl f32 f32_kernel }
+}
@@ -0,0 +1,439 @@
+use crate::math::exp;
+use crate::{LogAddExp, OnlineLogSumExp};
+
+/// A trait for computing the smooth maximum (and minimum) of a sequence at temperature
+/// `tau`, `tau * ln(sum_i(exp(x_i / tau)))`, i.e. [LogSumExp](https://en.wikipedia.org/wiki/LogSumExp)
+/// of the values scaled by `1 / tau`, in a numerically-stable manner, using the 1-pass
+/// (online) algorithm of [`LogSumExp`](crate::LogSumExp). The running maximum is kept on
+/// the unscaled values, hence, `x_i / tau` is never formed, and cannot overflow as
+/// `tau -> 0`.
+///
+/// The smooth maximum of `n` values is bounded by `max <= smooth_max <= max + tau * ln(n)`,
+/// and, at `tau = 0`, is exactly `max`. Special values are handled as in `LogSumExp`:
+/// `-inf` contributes nothing, `+inf` saturates the result and `nan` is absorbing; the
+/// smooth maximum of an empty sequence is `-inf`. The temperature must be finite and
+/// non-negative, else the result is `nan`.
+pub trait SmoothMax<T, U: Iterator<Item = T>> {
+    type Output;
+
+    /// Return the smooth maximum of the sequence at temperature `tau`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, SmoothMax};
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let rhs = 0.5 * v.iter().map(|x| x / 0.5).ln_sum_exp();
+    /// assert!((v.iter().smooth_max(0.5) - rhs).abs() < 1e-15);
+    /// assert_eq!(v.iter().smooth_max(1.0), v.iter().ln_sum_exp());
+    ///
+    /// // the hard maximum at tau = 0
+    /// assert_eq!(v.iter().smooth_max(0.0), 1.5);
+    ///
+    /// // x / tau would overflow
+    /// let v: Vec<f64> = vec![1e300, 1e300];
+    /// let tau = 1e-10;
+    /// assert_eq!(v.iter().smooth_max(tau), 1e300 + tau * 2.0_f64.ln());
+    /// ```
+    fn smooth_max(self, tau: Self::Output) -> Self::Output;
+
+    /// Return the smooth minimum of the sequence at temperature `tau`,
+    /// `-tau * ln(sum_i(exp(-x_i / tau)))`, i.e. the negation of the smooth maximum of
+    /// the negated values; the smooth minimum of an empty sequence is `+inf`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SmoothMax;
+    ///
+    /// let v: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let tau = 0.25;
+    /// let s = v.iter().smooth_min(tau);
+    /// assert!(0.5 - tau * 3.0_f64.ln() <= s && s <= 0.5);
+    /// assert_eq!(v.iter().smooth_min(0.0), 0.5);
+    /// ```
+    fn smooth_min(self, tau: Self::Output) -> Self::Output;
+}
+
+/// A trait for computing the smooth maximum (and minimum) of two values at temperature
+/// `tau`, `tau * ln(exp(a / tau) + exp(b / tau))`, by [`LogAddExp`](crate::LogAddExp),
+/// with its derivatives with respect to both values and `tau`; the pairwise counterpart
+/// of [`SmoothMax`], whose handling of special values it shares.
+///
+/// The derivatives with respect to `a` and `b` are the weights
+/// `p_a = exp((a - smooth_max) / tau)` and `p_b = 1 - p_a`, and the derivative with
+/// respect to `tau` is their entropy, `-(p_a * ln(p_a) + p_b * ln(p_b))`; at `tau = 0`, the
+/// derivatives are their limits as `tau -> 0+`, i.e. the hard maximum receives a weight
+/// of one, equal values each receive one half, and the derivative with respect to `tau`
+/// is `ln(2)` for equal finite values and zero otherwise.
+pub trait SmoothMax2<Rhs = Self> {
+    type Output;
+
+    /// Return the smooth maximum of `self` and `rhs` at temperature `tau`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogAddExp, SmoothMax2};
+    ///
+    /// let a: f64 = 0.5;
+    /// let b: f64 = 1.5;
+    /// assert_eq!(a.smooth_max2(b, 1.0), a.ln_add_exp(b));
+    /// assert_eq!(a.smooth_max2(&b, 0.0), 1.5);
+    /// assert_eq!(a.smooth_max2(a, 0.25), a + 0.25 * 2.0_f64.ln());
+    /// ```
+    fn smooth_max2(&self, rhs: Rhs, tau: Self::Output) -> Self::Output;
+
+    /// Return the smooth minimum of `self` and `rhs` at temperature `tau`,
+    /// `-tau * ln(exp(-a / tau) + exp(-b / tau))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SmoothMax2;
+    ///
+    /// let a: f64 = 0.5;
+    /// let b: f64 = 1.5;
+    /// assert_eq!(a.smooth_min2(b, 1.0), -(-a).smooth_max2(-b, 1.0));
+    /// assert_eq!(a.smooth_min2(b, 0.0), 0.5);
+    /// ```
+    fn smooth_min2(&self, rhs: Rhs, tau: Self::Output) -> Self::Output;
+
+    /// Return the smooth maximum of `self` and `rhs` at temperature `tau`, and its
+    /// derivatives with respect to `self`, `rhs` and `tau`, in that order.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SmoothMax2;
+    ///
+    /// let (a, b, tau): (f64, f64, f64) = (0.5, 1.5, 0.5);
+    /// let (s, d_a, d_b, d_tau) = a.smooth_max2_with_grad(b, tau);
+    /// assert_eq!(s, a.smooth_max2(b, tau));
+    /// assert!((d_a + d_b - 1.0).abs() < 1e-15);
+    ///
+    /// let h = 1e-6;
+    /// let fd = (a.smooth_max2(b, tau + h) - a.smooth_max2(b, tau - h)) / (2.0 * h);
+    /// assert!((d_tau - fd).abs() < 1e-8);
+    /// ```
+    fn smooth_max2_with_grad(
+        &self,
+        rhs: Rhs,
+        tau: Self::Output,
+    ) -> (Self::Output, Self::Output, Self::Output, Self::Output);
+
+    /// Return the smooth minimum of `self` and `rhs` at temperature `tau`, and its
+    /// derivatives with respect to `self`, `rhs` and `tau`, in that order; the latter is
+    /// the negated entropy of the weights.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::SmoothMax2;
+    ///
+    /// let (a, b): (f64, f64) = (0.5, 1.5);
+    /// let (s, d_a, d_b, d_tau) = a.smooth_min2_with_grad(b, 0.0);
+    /// assert_eq!((s, d_a, d_b, d_tau), (0.5, 1.0, 0.0, 0.0));
+    /// ```
+    fn smooth_min2_with_grad(
+        &self,
+        rhs: Rhs,
+        tau: Self::Output,
+    ) -> (Self::Output, Self::Output, Self::Output, Self::Output);
+}
+
+macro_rules! impl_smooth_max {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> SmoothMax<$f, U> for U
+            where
+                U: Iterator<Item = $f>,
+            {
+                type Output = $f;
+
+                fn smooth_max(self, tau: $f) -> Self::Output {
+                    if !(tau.is_finite() && tau >= 0.0) {
+                        return $f::NAN;
+                    }
+                    if tau == 0.0 {
+                        // The hard maximum, to which nan is absorbing.
+                        let mut max = $f::NEG_INFINITY;
+                        for x in self {
+                            if x.is_nan() {
+                                return x;
+                            }
+                            max = max.max(x);
+                        }
+                        return max;
+                    }
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    for x in self {
+                        acc.push_scaled(x, tau);
+                    }
+                    acc.finish_scaled(tau)
+                }
+
+                fn smooth_min(self, tau: $f) -> Self::Output {
+                    -self.map(|x| -x).smooth_max(tau)
+                }
+            }
+
+            impl<'a, U> SmoothMax<&'a $f, U> for U
+            where
+                U: Iterator<Item = &'a $f>,
+            {
+                type Output = $f;
+
+                fn smooth_max(self, tau: $f) -> Self::Output {
+                    self.copied().smooth_max(tau)
+                }
+
+                fn smooth_min(self, tau: $f) -> Self::Output {
+                    self.copied().smooth_min(tau)
+                }
+            }
+
+            impl SmoothMax2 for $f {
+                type Output = $f;
+
+                fn smooth_max2(&self, rhs: Self, tau: $f) -> Self::Output {
+                    self.smooth_max2_with_grad(rhs, tau).0
+                }
+
+                fn smooth_min2(&self, rhs: Self, tau: $f) -> Self::Output {
+                    -(-*self).smooth_max2(-rhs, tau)
+                }
+
+                fn smooth_max2_with_grad(&self, rhs: Self, tau: $f) -> ($f, $f, $f, $f) {
+                    let (a, b) = (*self, rhs);
+                    if !(tau.is_finite() && tau >= 0.0) || a.is_nan() || b.is_nan() {
+                        let nan = $f::NAN;
+                        return (nan, nan, nan, nan);
+                    }
+                    if a == b {
+                        // Equal infinities are the value for every `tau`.
+                        return if a.is_finite() {
+                            (a + tau * core::$f::consts::LN_2, 0.5, 0.5, core::$f::consts::LN_2)
+                        } else {
+                            (a, 0.5, 0.5, 0.0)
+                        };
+                    }
+                    let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+                    let (value, p_lo, d_tau) = if hi.is_infinite() || lo == $f::NEG_INFINITY || tau == 0.0 {
+                        (hi, 0.0, 0.0)
+                    } else {
+                        // ln(1 + exp(d)), with d = (lo - hi) / tau <= 0
+                        let d = (lo - hi) / tau;
+                        let l = (0.0 as $f).ln_add_exp(d);
+                        let p_lo = exp(d - l);
+                        let d_tau = if p_lo == 0.0 { l } else { l - p_lo * d };
+                        (hi + tau * l, p_lo, d_tau)
+                    };
+                    let p_hi = 1.0 - p_lo;
+                    if a > b {
+                        (value, p_hi, p_lo, d_tau)
+                    } else {
+                        (value, p_lo, p_hi, d_tau)
+                    }
+                }
+
+                fn smooth_min2_with_grad(&self, rhs: Self, tau: $f) -> ($f, $f, $f, $f) {
+                    let (value, d_a, d_b, d_tau) = (-*self).smooth_max2_with_grad(-rhs, tau);
+                    (-value, d_a, d_b, -d_tau)
+                }
+            }
+
+            impl SmoothMax2<&$f> for $f {
+                type Output = $f;
+
+                fn smooth_max2(&self, rhs: &$f, tau: $f) -> Self::Output {
+                    self.smooth_max2(*rhs, tau)
+                }
+
+                fn smooth_min2(&self, rhs: &$f, tau: $f) -> Self::Output {
+                    self.smooth_min2(*rhs, tau)
+                }
+
+                fn smooth_max2_with_grad(&self, rhs: &$f, tau: $f) -> ($f, $f, $f, $f) {
+                    self.smooth_max2_with_grad(*rhs, tau)
+                }
+
+                fn smooth_min2_with_grad(&self, rhs: &$f, tau: $f) -> ($f, $f, $f, $f) {
+                    self.smooth_min2_with_grad(*rhs, tau)
+                }
+            }
+        )+
+    }
+}
+impl_smooth_max! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::values;
+    use crate::LogSumExp;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! smooth_max_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                fn hard_max(v: &[$f]) -> $f {
+                    v.iter().copied().fold($f::NEG_INFINITY, $f::max)
+                }
+
+                fn hard_min(v: &[$f]) -> $f {
+                    v.iter().copied().fold($f::INFINITY, $f::min)
+                }
+
+                const TOL: $f = 16.0 * $f::EPSILON;
+
+                #[test]
+                fn smooth_max_works() {
+                    let v = values::<$f>(100, -5.0, 5.0);
+                    for tau in [0.5, 1.0, 4.0] {
+                        let rhs = tau * v.iter().map(|x| x / tau).ln_sum_exp();
+                        assert!((v.iter().smooth_max(tau) - rhs).abs() <= TOL * rhs.abs().max(1.0));
+                        assert_eq!(v.clone().into_iter().smooth_max(tau), v.iter().smooth_max(tau));
+                        let rhs = -tau * v.iter().map(|x| -x / tau).ln_sum_exp();
+                        assert!((v.iter().smooth_min(tau) - rhs).abs() <= TOL * rhs.abs().max(1.0));
+                    }
+                    assert_eq!(v.iter().smooth_max(0.0), hard_max(&v));
+                    assert_eq!(v.iter().smooth_min(0.0), hard_min(&v));
+                }
+
+                #[test]
+                fn approximation_bound() {
+                    for n in [1, 2, 10, 1000] {
+                        let v = values::<$f>(n, -100.0, 100.0);
+                        let (max, min) = (hard_max(&v), hard_min(&v));
+                        let ln_n = (n as $f).ln();
+                        for tau in [1e-30, 1e-3, 0.1, 1.0, 10.0, 1e3] {
+                            let s = v.iter().smooth_max(tau);
+                            assert!(max <= s, "{} {} {}", n, tau, s);
+                            assert!(s <= max + tau * ln_n + TOL * max.abs().max(tau * ln_n), "{} {} {}", n, tau, s);
+                            let s = v.iter().smooth_min(tau);
+                            assert!(s <= min, "{} {} {}", n, tau, s);
+                            assert!(min - tau * ln_n - TOL * min.abs().max(tau * ln_n) <= s, "{} {} {}", n, tau, s);
+                        }
+                    }
+                    // the upper bound is attained by equal values
+                    let v = vec![2.0 as $f; 8];
+                    let s = v.iter().smooth_max(0.5);
+                    assert!((s - (2.0 + 0.5 * (8.0 as $f).ln())).abs() <= TOL * 4.0);
+                }
+
+                #[test]
+                fn tau_to_zero() {
+                    let v = values::<$f>(10, -1.0, 1.0);
+                    let max = hard_max(&v);
+                    // x / tau overflows
+                    let tau = $f::MIN_POSITIVE;
+                    assert_eq!(v.iter().smooth_max(tau), max);
+                    let big = $f::MAX / 2.0;
+                    assert_eq!([big, -big].iter().smooth_max(1e-3), big);
+                    assert_eq!([big, big].iter().smooth_max(1e-3), big);
+                    assert_eq!([big, -big].iter().smooth_min(1e-3), -big);
+                    // monotone convergence
+                    let mut prev = $f::INFINITY;
+                    for tau in [1.0, 0.1, 0.01, 0.001] {
+                        let s = v.iter().smooth_max(tau);
+                        assert!(s <= prev && max <= s);
+                        prev = s;
+                    }
+                }
+
+                #[test]
+                fn special_values() {
+                    let (inf, neg_inf, nan) = ($f::INFINITY, $f::NEG_INFINITY, $f::NAN);
+                    let e: Vec<$f> = vec![];
+                    for tau in [0.0, 1.0] {
+                        assert_eq!(e.iter().smooth_max(tau), neg_inf);
+                        assert_eq!(e.iter().smooth_min(tau), inf);
+                        assert_eq!([1.0, neg_inf].iter().smooth_max(tau), 1.0);
+                        assert_eq!([neg_inf, neg_inf].iter().smooth_max(tau), neg_inf);
+                        assert_eq!([1.0, inf].iter().smooth_max(tau), inf);
+                        assert_eq!([inf, 1.0].iter().smooth_min(tau), 1.0);
+                        assert!([1.0, nan, inf].iter().smooth_max(tau).is_nan());
+                        assert!([inf, nan].iter().smooth_max(tau).is_nan());
+                    }
+                    for tau in [-1.0, inf, nan] {
+                        assert!([1.0 as $f, 2.0].iter().smooth_max(tau).is_nan());
+                        assert!((1.0 as $f).smooth_max2(2.0, tau).is_nan());
+                    }
+                }
+
+                #[test]
+                fn smooth_max2_works() {
+                    let v = values::<$f>(40, -10.0, 10.0);
+                    for w in v.chunks_exact(2) {
+                        let (a, b) = (w[0], w[1]);
+                        for tau in [0.0, 0.01, 0.5, 1.0, 10.0] {
+                            let s = a.smooth_max2(b, tau);
+                            assert!((s - [a, b].iter().smooth_max(tau)).abs() <= TOL * s.abs().max(1.0));
+                            assert_eq!(a.smooth_max2(b, tau), b.smooth_max2(&a, tau));
+                            let s = a.smooth_min2(b, tau);
+                            assert!((s - [a, b].iter().smooth_min(tau)).abs() <= TOL * s.abs().max(1.0));
+                        }
+                        assert_eq!(a.smooth_max2(b, 1.0), a.ln_add_exp(b));
+                        assert_eq!(a.smooth_max2(b, 0.0), a.max(b));
+                        assert_eq!(a.smooth_min2(b, 0.0), a.min(b));
+                    }
+
+                    let (inf, neg_inf) = ($f::INFINITY, $f::NEG_INFINITY);
+                    assert_eq!((1.0 as $f).smooth_max2(neg_inf, 1.0), 1.0);
+                    assert_eq!(neg_inf.smooth_max2(neg_inf, 1.0), neg_inf);
+                    assert_eq!(inf.smooth_max2(inf, 1.0), inf);
+                    assert_eq!((1.0 as $f).smooth_min2(inf, 1.0), 1.0);
+                    assert!((1.0 as $f).smooth_max2($f::NAN, 0.0).is_nan());
+                }
+
+                #[test]
+                fn smooth_max2_grad_works() {
+                    let h: $f = if $f::EPSILON < 1e-10 { 1e-6 } else { 1e-2 };
+                    let tol: $f = if $f::EPSILON < 1e-10 { 1e-7 } else { 1e-2 };
+                    let v = values::<$f>(40, -2.0, 2.0);
+                    for w in v.chunks_exact(2) {
+                        let (a, b) = (w[0], w[1]);
+                        for tau in [0.25, 1.0, 3.0] {
+                            for min in [false, true] {
+                                let (f, g): (fn(&$f, $f, $f) -> $f, fn(&$f, $f, $f) -> ($f, $f, $f, $f)) = if min {
+                                    (<$f as SmoothMax2>::smooth_min2, <$f as SmoothMax2>::smooth_min2_with_grad)
+                                } else {
+                                    (<$f as SmoothMax2>::smooth_max2, <$f as SmoothMax2>::smooth_max2_with_grad)
+                                };
+                                let (s, d_a, d_b, d_tau) = g(&a, b, tau);
+                                assert_eq!(s, f(&a, b, tau));
+                                assert!((d_a + d_b - 1.0).abs() <= TOL);
+                                let fd_a = (f(&(a + h), b, tau) - f(&(a - h), b, tau)) / (2.0 * h);
+                                let fd_b = (f(&a, b + h, tau) - f(&a, b - h, tau)) / (2.0 * h);
+                                let fd_tau = (f(&a, b, tau + h) - f(&a, b, tau - h)) / (2.0 * h);
+                                assert!((d_a - fd_a).abs() <= tol, "{} {} {}", d_a, fd_a, tau);
+                                assert!((d_b - fd_b).abs() <= tol, "{} {} {}", d_b, fd_b, tau);
+                                assert!((d_tau - fd_tau).abs() <= tol, "{} {} {}", d_tau, fd_tau, tau);
+                            }
+                        }
+                    }
+
+                    // the limits as tau -> 0+
+                    let ln_2 = core::$f::consts::LN_2;
+                    assert_eq!((1.0 as $f).smooth_max2_with_grad(2.0, 0.0), (2.0, 0.0, 1.0, 0.0));
+                    assert_eq!((1.0 as $f).smooth_max2_with_grad(1.0, 0.0), (1.0, 0.5, 0.5, ln_2));
+                    assert_eq!((1.0 as $f).smooth_min2_with_grad(1.0, 0.0), (1.0, 0.5, 0.5, -ln_2));
+                    let (_, d_a, d_b, d_tau) = (1.0 as $f).smooth_max2_with_grad(2.0, 1e-3);
+                    assert_eq!((d_a, d_b, d_tau), (0.0, 1.0, 0.0));
+                    let (_, d_a, d_b, d_tau) = (1.0 as $f).smooth_max2_with_grad($f::NEG_INFINITY, 1.0);
+                    assert_eq!((d_a, d_b, d_tau), (1.0, 0.0, 0.0));
+
+                    // equal infinities, which do not depend on tau
+                    for x in [$f::NEG_INFINITY, $f::INFINITY] {
+                        for tau in [0.0, 1.0] {
+                            assert_eq!(x.smooth_max2_with_grad(x, tau), (x, 0.5, 0.5, 0.0));
+                            assert_eq!(x.smooth_min2_with_grad(x, tau), (x, 0.5, 0.5, 0.0));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    smooth_max_tests! { f64_smooth_max_impl f64 }
+    smooth_max_tests! { f32_smooth_max_impl f32 }
+}
@@ -0,0 +1,231 @@
+use crate::math::{exp, ln};
+use crate::LogSumExp;
+use alloc::vec::Vec;
+
+/// A trait for computing the [softmax](https://en.wikipedia.org/wiki/Softmax_function)
+/// of a slice, and its logarithm, using the normalizer computed by the 1-pass (online)
+/// algorithm of [`LogSumExp`], followed by a single normalization pass; this is the
+/// 2-pass softmax of [Milakov, Maxim, and Natalia Gimelshein. "Online normalizer calculation for softmax." (2018)](https://arxiv.org/pdf/1805.02867.pdf).
+///
+/// Special values are handled consistently with `LogSumExp`:
+/// - `-inf` elements have probability zero (log-probability `-inf`).
+/// - If any element is `+inf`, the `k` elements which are `+inf` share the probability
+///   equally, i.e. each is `1/k`, and all other elements are zero; this is the limit
+///   as the `+inf` elements tend to infinity together.
+/// - If any element is `nan`, all elements of the result are `nan`.
+/// - If the slice is non-empty and all elements are `-inf`, the normalizer is zero,
+///   hence, all elements of the result are `nan`.
+pub trait Softmax {
+    type Output;
+
+    /// Return the softmax of the slice, i.e. `exp(x_i) / sum(exp(x_j))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::Softmax;
+    ///
+    /// let v: Vec<f64> = vec![0.5_f64.ln(), 0.25_f64.ln(), 0.25_f64.ln()];
+    /// let p = v.softmax();
+    /// assert!((p[0] - 0.5).abs() < f64::EPSILON);
+    /// assert!((p[1] - 0.25).abs() < f64::EPSILON);
+    ///
+    /// // large values which would overflow the naive computation
+    /// let v: Vec<f64> = vec![1000.0, 1000.0];
+    /// assert!(v.softmax().iter().all(|p| (p - 0.5).abs() < 1e-12));
+    ///
+    /// // +inf takes all the probability
+    /// let v: Vec<f64> = vec![f64::INFINITY, 1.0, f64::NEG_INFINITY];
+    /// assert_eq!(v.softmax(), vec![1.0, 0.0, 0.0]);
+    /// ```
+    fn softmax(&self) -> Self::Output;
+
+    /// Return the log of the softmax of the slice, i.e. `x_i - ln(sum(exp(x_j)))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::Softmax;
+    ///
+    /// let v: Vec<f64> = vec![2.0, 2.0, f64::NEG_INFINITY];
+    /// let lp = v.log_softmax();
+    /// assert!((lp[0] + 2.0_f64.ln()).abs() < f64::EPSILON);
+    /// assert_eq!(lp[0], lp[1]);
+    /// assert_eq!(lp[2], f64::NEG_INFINITY);
+    /// ```
+    fn log_softmax(&self) -> Self::Output;
+
+    /// Replace each element of the slice by its softmax.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::Softmax;
+    ///
+    /// let mut v: Vec<f32> = vec![1.0, 1.0, 1.0, 1.0];
+    /// v.softmax_mut();
+    /// assert_eq!(v, vec![0.25; 4]);
+    /// ```
+    fn softmax_mut(&mut self);
+
+    /// Replace each element of the slice by its log-softmax.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::Softmax;
+    ///
+    /// let mut v: Vec<f32> = vec![0.0, f32::NAN];
+    /// v.log_softmax_mut();
+    /// assert!(v.iter().all(|x| x.is_nan()));
+    /// ```
+    fn log_softmax_mut(&mut self);
+}
+
+macro_rules! impl_softmax {
+    { $($f:ident)+ } => {
+        $(
+            impl Softmax for [$f] {
+                type Output = Vec<$f>;
+                fn softmax(&self) -> Self::Output {
+                    let mut v = self.to_vec();
+                    v.softmax_mut();
+                    v
+                }
+
+                fn log_softmax(&self) -> Self::Output {
+                    let mut v = self.to_vec();
+                    v.log_softmax_mut();
+                    v
+                }
+
+                fn softmax_mut(&mut self) {
+                    let lse = self.iter().ln_sum_exp();
+                    if lse == $f::INFINITY {
+                        let k = self.iter().filter(|x| **x == $f::INFINITY).count() as $f;
+                        let p = k.recip();
+                        self.iter_mut().for_each(|x| *x = if *x == $f::INFINITY { p } else { 0.0 });
+                    } else {
+                        self.iter_mut().for_each(|x| *x = exp(*x - lse));
+                    }
+                }
+
+                fn log_softmax_mut(&mut self) {
+                    let lse = self.iter().ln_sum_exp();
+                    if lse == $f::INFINITY {
+                        let k = self.iter().filter(|x| **x == $f::INFINITY).count() as $f;
+                        let ln_p = -ln(k);
+                        self.iter_mut().for_each(|x| {
+                            *x = if *x == $f::INFINITY { ln_p } else { $f::NEG_INFINITY }
+                        });
+                    } else {
+                        self.iter_mut().for_each(|x| *x -= lse);
+                    }
+                }
+            }
+        )+
+    }
+}
+impl_softmax! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    macro_rules! softmax_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn softmax_works() {
+                    let v: Vec<$f> = vec![0.5, 1.0, 1.5, -2.0];
+                    let denom: $f = v.iter().map(|x| x.exp()).sum();
+                    let p = v.softmax();
+                    for (p_i, x_i) in p.iter().zip(v.iter()) {
+                        assert!((p_i - x_i.exp() / denom).abs() < 2.0 * $f::EPSILON);
+                    }
+                    assert!((p.iter().sum::<$f>() - 1.0).abs() < 4.0 * $f::EPSILON);
+
+                    let mut w = v.clone();
+                    w.softmax_mut();
+                    assert_eq!(w, p);
+
+                    // shift invariance
+                    let u: Vec<$f> = v.iter().map(|x| x + 100.0).collect();
+                    let q = u.softmax();
+                    for (p_i, q_i) in p.iter().zip(q.iter()) {
+                        assert!((p_i - q_i).abs() < 128.0 * $f::EPSILON);
+                    }
+                }
+
+                #[test]
+                fn log_softmax_works() {
+                    let v: Vec<$f> = vec![0.5, 1.0, 1.5, -2.0];
+                    let lse = v.iter().ln_sum_exp();
+                    let lp = v.log_softmax();
+                    for (lp_i, x_i) in lp.iter().zip(v.iter()) {
+                        assert_eq!(*lp_i, x_i - lse);
+                    }
+
+                    let mut w = v.clone();
+                    w.log_softmax_mut();
+                    assert_eq!(w, lp);
+
+                    let p = v.softmax();
+                    for (lp_i, p_i) in lp.iter().zip(p.iter()) {
+                        assert_eq!(lp_i.exp(), *p_i);
+                    }
+                }
+
+                #[test]
+                fn softmax_special_values() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+
+                    // -inf
+                    let v = vec![neg_inf, x, neg_inf];
+                    assert_eq!(v.softmax(), vec![0.0, 1.0, 0.0]);
+                    assert_eq!(v.log_softmax(), vec![neg_inf, 0.0, neg_inf]);
+
+                    // +inf
+                    let v = vec![inf, x, neg_inf];
+                    assert_eq!(v.softmax(), vec![1.0, 0.0, 0.0]);
+                    assert_eq!(v.log_softmax(), vec![0.0, neg_inf, neg_inf]);
+
+                    let v = vec![inf, x, inf, inf, neg_inf];
+                    let p: $f = 1.0 / 3.0;
+                    assert_eq!(v.softmax(), vec![p, 0.0, p, p, 0.0]);
+                    let lp: $f = -crate::math::ln(3.0 as $f);
+                    assert_eq!(v.log_softmax(), vec![lp, neg_inf, lp, lp, neg_inf]);
+
+                    // nan
+                    let v = vec![inf, x, nan];
+                    assert!(v.softmax().iter().all(|p| p.is_nan()));
+                    assert!(v.log_softmax().iter().all(|p| p.is_nan()));
+
+                    let v = vec![neg_inf, x, nan];
+                    assert!(v.softmax().iter().all(|p| p.is_nan()));
+                    assert!(v.log_softmax().iter().all(|p| p.is_nan()));
+
+                    // all -inf
+                    let v = vec![neg_inf; 3];
+                    assert!(v.softmax().iter().all(|p| p.is_nan()));
+                    assert!(v.log_softmax().iter().all(|p| p.is_nan()));
+
+                    // Edge cases
+                    let v: Vec<$f> = vec![];
+                    assert!(v.softmax().is_empty());
+                    assert!(v.log_softmax().is_empty());
+
+                    let v: Vec<$f> = vec![x];
+                    assert_eq!(v.softmax(), vec![1.0]);
+                    assert_eq!(v.log_softmax(), vec![0.0]);
+                }
+            }
+        }
+    }
+    softmax_tests! { f64_softmax_impl f64 }
+    softmax_tests! { f32_softmax_impl f32 }
+}
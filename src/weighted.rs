@@ -0,0 +1,228 @@
+use crate::OnlineLogSumExp;
+
+/// A trait for computing the log of the weighted sum of exponentials of a sequence,
+/// `ln(w_1 * exp(x_1) + w_2 * exp(x_2) + ...)`, in a numerically-stable manner,
+/// using the same 1-pass (online) algorithm as [`LogSumExp`](crate::LogSumExp).
+/// The sequence consists of `(value, weight)` pairs.
+///
+/// Terms with zero weight are excluded, irrespective of their value; otherwise,
+/// +/-infinity and `nan` values are handled as in `LogSumExp`.
+pub trait WeightedLogSumExp<T, U: Iterator<Item = (T, T)>> {
+    type Output;
+
+    /// Return the log of the weighted sum of exponentials of the sequence, where the
+    /// weights are on the linear scale. A negative or `nan` weight results in `nan`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::WeightedLogSumExp;
+    ///
+    /// let x: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let w: Vec<f64> = vec![2.0, 0.0, 3.0];
+    /// let rhs: f64 = (2.0 * 0.5_f64.exp() + 3.0 * 1.5_f64.exp()).ln();
+    /// assert!((x.iter().zip(w.iter()).ln_sum_exp_weighted() - rhs).abs() < 4.0 * f64::EPSILON);
+    ///
+    /// // a term with zero weight does not participate
+    /// let v: Vec<(f64, f64)> = vec![(f64::INFINITY, 0.0), (0.5, 1.0)];
+    /// assert_eq!(v.into_iter().ln_sum_exp_weighted(), 0.5);
+    ///
+    /// // negative weights are not permitted
+    /// let v: Vec<(f64, f64)> = vec![(0.5, -1.0), (0.5, 2.0)];
+    /// assert!(v.into_iter().ln_sum_exp_weighted().is_nan());
+    /// ```
+    fn ln_sum_exp_weighted(self) -> Self::Output;
+
+    /// Return the log of the weighted sum of exponentials of the sequence, where the
+    /// weights are on the log scale, i.e. the result is `ln(exp(x_1 + ln_w_1) + ...)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use logsumexp::{LogSumExp, WeightedLogSumExp};
+    ///
+    /// let x: Vec<f64> = vec![0.5, 1.0, 1.5];
+    /// let ln_w: Vec<f64> = vec![-1.0, f64::NEG_INFINITY, 2.0];
+    /// let rhs: f64 = [0.5 - 1.0, 1.5 + 2.0].iter().ln_sum_exp();
+    /// assert_eq!(x.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted(), rhs);
+    /// ```
+    fn ln_sum_exp_ln_weighted(self) -> Self::Output;
+}
+
+macro_rules! impl_weighted_logsumexp {
+    { $($f:ident)+ } => {
+        $(
+            impl<U> WeightedLogSumExp<$f, U> for U
+            where
+                U: Iterator<Item = ($f, $f)>,
+            {
+                type Output = $f;
+                fn ln_sum_exp_weighted(self) -> Self::Output {
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    for (x, w) in self {
+                        acc.push_weighted(x, w);
+                    }
+                    acc.finish()
+                }
+
+                fn ln_sum_exp_ln_weighted(self) -> Self::Output {
+                    let mut acc = OnlineLogSumExp::<$f>::new();
+                    for (x, ln_w) in self {
+                        acc.push_ln_weighted(x, ln_w);
+                    }
+                    acc.finish()
+                }
+            }
+
+            impl<'a, U> WeightedLogSumExp<&'a $f, U> for U
+            where
+                U: Iterator<Item = (&'a $f, &'a $f)>,
+            {
+                type Output = $f;
+                fn ln_sum_exp_weighted(self) -> Self::Output {
+                    self.map(|(x, w)| (*x, *w)).ln_sum_exp_weighted()
+                }
+
+                fn ln_sum_exp_ln_weighted(self) -> Self::Output {
+                    self.map(|(x, ln_w)| (*x, *ln_w)).ln_sum_exp_ln_weighted()
+                }
+            }
+        )+
+    }
+}
+impl_weighted_logsumexp! { f64 f32 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LogAddExp, LogSumExp};
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    macro_rules! weighted_logsumexp_tests {
+        { $name:ident $f:ident } => {
+            #[cfg(test)]
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn ln_sum_exp_weighted_works() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    // unit weights are equivalent to the unweighted sum
+                    let v = vec![neg_inf, x, y, neg_inf];
+                    let w: Vec<$f> = vec![1.0; 4];
+                    assert_eq!(v.iter().zip(w.iter()).ln_sum_exp_weighted(), v.iter().ln_sum_exp());
+
+                    let v = vec![x, y];
+                    let w: Vec<$f> = vec![2.0, 3.0];
+                    let rhs: $f = (2.0 * x.exp() + 3.0 * y.exp()).ln();
+                    let lhs = v.iter().zip(w.iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() < 2.0 * $f::EPSILON);
+                    let lhs = v.into_iter().zip(w.into_iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() < 2.0 * $f::EPSILON);
+
+                    // zero weights
+                    let v = vec![x, inf, nan, neg_inf];
+                    let w: Vec<$f> = vec![1.0, 0.0, 0.0, 0.0];
+                    assert_eq!(v.iter().zip(w.iter()).ln_sum_exp_weighted(), x);
+
+                    let v = vec![x, y];
+                    let w: Vec<$f> = vec![0.0, 0.0];
+                    assert_eq!(v.iter().zip(w.iter()).ln_sum_exp_weighted(), neg_inf);
+
+                    // +/-inf values
+                    let v = vec![x, inf, y];
+                    let w: Vec<$f> = vec![1.0, 0.5, 2.0];
+                    assert_eq!(v.iter().zip(w.iter()).ln_sum_exp_weighted(), inf);
+
+                    let v = vec![x, neg_inf, y];
+                    let w: Vec<$f> = vec![1.0, 0.5, 2.0];
+                    let rhs: $f = (x.exp() + 2.0 * y.exp()).ln();
+                    let lhs = v.iter().zip(w.iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() < 2.0 * $f::EPSILON);
+
+                    // infinite weights
+                    let v = vec![x, y];
+                    let w: Vec<$f> = vec![1.0, inf];
+                    assert_eq!(v.iter().zip(w.iter()).ln_sum_exp_weighted(), inf);
+
+                    let v = vec![x, neg_inf];
+                    let w: Vec<$f> = vec![1.0, inf];
+                    assert!(v.iter().zip(w.iter()).ln_sum_exp_weighted().is_nan());
+
+                    // Cases involving nan
+                    let v = vec![x, nan, y];
+                    let w: Vec<$f> = vec![1.0, 1.0, 1.0];
+                    assert!(v.iter().zip(w.iter()).ln_sum_exp_weighted().is_nan());
+
+                    let v = vec![x, inf, y];
+                    let w: Vec<$f> = vec![1.0, 1.0, nan];
+                    assert!(v.iter().zip(w.iter()).ln_sum_exp_weighted().is_nan());
+
+                    let v = vec![x, inf, y];
+                    let w: Vec<$f> = vec![1.0, 1.0, -1.0];
+                    assert!(v.iter().zip(w.iter()).ln_sum_exp_weighted().is_nan());
+
+                    // weights near MAX, for which the linear-scale sum would overflow
+                    let v: Vec<$f> = vec![0.0, 0.0];
+                    let w: Vec<$f> = vec![$f::MAX, $f::MAX];
+                    let rhs: $f = $f::MAX.ln() + (2.0 as $f).ln();
+                    let lhs = v.iter().zip(w.iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() <= 2.0 * $f::EPSILON * rhs);
+                    let v: Vec<$f> = vec![1.0, -1.0, 0.5];
+                    let w: Vec<$f> = vec![$f::MAX, $f::MAX / 4.0, 1.0];
+                    let rhs: $f = $f::MAX.ln() + (1.0 as $f).ln_add_exp((-1.0 as $f) - (4.0 as $f).ln());
+                    let lhs = v.iter().zip(w.iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() <= 4.0 * $f::EPSILON * rhs);
+
+                    // Edge cases
+                    let v: Vec<($f, $f)> = vec![];
+                    assert_eq!(v.into_iter().ln_sum_exp_weighted(), neg_inf);
+                }
+
+                #[test]
+                fn ln_sum_exp_ln_weighted_works() {
+                    let inf: $f = $f::INFINITY;
+                    let neg_inf: $f = $f::NEG_INFINITY;
+                    let nan: $f = $f::NAN;
+                    let x: $f = 0.5;
+                    let y: $f = 1.0;
+
+                    let v = vec![x, y, neg_inf];
+                    let ln_w: Vec<$f> = vec![0.0; 3];
+                    assert_eq!(v.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted(), v.iter().ln_sum_exp());
+
+                    let v = vec![x, y];
+                    let w: Vec<$f> = vec![2.0, 3.0];
+                    let ln_w: Vec<$f> = w.iter().map(|w| w.ln()).collect();
+                    let lhs = v.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted();
+                    let rhs = v.iter().zip(w.iter()).ln_sum_exp_weighted();
+                    assert!((lhs - rhs).abs() < 2.0 * $f::EPSILON);
+
+                    // zero weights
+                    let v = vec![x, inf, nan];
+                    let ln_w: Vec<$f> = vec![0.0, neg_inf, neg_inf];
+                    assert_eq!(v.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted(), x);
+
+                    // +/-inf and nan
+                    let v = vec![x, inf];
+                    let ln_w: Vec<$f> = vec![0.0, -1.0];
+                    assert_eq!(v.into_iter().zip(ln_w.into_iter()).ln_sum_exp_ln_weighted(), inf);
+
+                    let v = vec![x, y];
+                    let ln_w: Vec<$f> = vec![0.0, nan];
+                    assert!(v.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted().is_nan());
+
+                    let v = vec![x, neg_inf];
+                    let ln_w: Vec<$f> = vec![0.0, inf];
+                    assert!(v.iter().zip(ln_w.iter()).ln_sum_exp_ln_weighted().is_nan());
+                }
+            }
+        }
+    }
+    weighted_logsumexp_tests! { f64_weighted_logsumexp_impl f64 }
+    weighted_logsumexp_tests! { f32_weighted_logsumexp_impl f32 }
+}